@@ -3,6 +3,9 @@
 use std::path::PathBuf;
 
 use clap::Parser;
+use teamsearch_utils::{highlight::ColourChoice, logging::MessagingFormat};
+
+use crate::{colours::ColourSpec, printer::template::Template};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -15,12 +18,62 @@ use clap::Parser;
 pub struct Cli {
     #[command(subcommand)]
     pub(crate) command: Command,
+
+    /// When to highlight the output with colours. By default, the output is
+    /// only highlighted when it's written to a terminal and the `NO_COLOR`
+    /// environment variable isn't set, and setting `CLICOLOR_FORCE` forces
+    /// the output to be highlighted.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_name = "WHEN",
+        help = "When to highlight the output with colours [default: auto]"
+    )]
+    pub(crate) color: Option<ColourChoice>,
+
+    /// The format of the diagnostic messages, such as the summary of a search
+    /// or errors. With `json`, each message is written to `stderr` as a JSON
+    /// object with the `level`, `message` and `fields` of the message.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t,
+        value_name = "FORMAT",
+        help = "The format of the diagnostic messages"
+    )]
+    pub(crate) message_format: MessagingFormat,
+
+    /// The number of threads that are used to walk and search the files. By
+    /// default, this is based on the number of available CPUs.
+    #[arg(
+        long,
+        short = 'j',
+        global = true,
+        value_name = "NUM",
+        help = "The number of threads that are used to walk and search the files"
+    )]
+    pub(crate) threads: Option<usize>,
+
+    /// The team registry, a YAML, JSON or TOML file that maps each of the
+    /// owners to their display name, chat channel, on-call alias, email
+    /// address and members. The metadata of the owners is shown by `lookup`
+    /// and `find --show-owners`, and `validate` checks that all of the owners
+    /// are in the registry.
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "The team registry that holds the metadata of the owners"
+    )]
+    pub(crate) registry: Option<PathBuf>,
 }
 
 #[derive(Debug, clap::Subcommand)]
 pub enum Command {
     /// Find the code that you're looking for based on the CODEOWNERS file.
-    Find(FindCommand),
+    Find(Box<FindCommand>),
 
     /// Lookup the team that owns a specific file or directory.
     Lookup(LookupCommand),
@@ -28,10 +81,57 @@ pub enum Command {
     /// Find orphaned files that don't belong to any team.
     Orphans(OrphanCommand),
 
+    /// Show how the owners of a path changed over the history of the
+    /// CODEOWNERS file.
+    History(HistoryCommand),
+
+    /// Check that all of the files that are added by the staged changes are
+    /// owned by a team, which is meant to be run from a pre-commit hook.
+    CheckStaged(CheckStagedCommand),
+
+    /// Suggest owners for the orphaned files, based on the teams of the
+    /// people that have been committing to them.
+    Suggest(SuggestCommand),
+
+    /// Check that all of the owners of the CODEOWNERS file are in the team
+    /// registry.
+    ///
+    /// Only the given CODEOWNERS file is checked, so the CODEOWNERS files of
+    /// nested repositories have to be checked by running `validate` with each
+    /// of them.
+    Validate(ValidateCommand),
+
+    /// Inspect the configuration that is loaded from the `teamsearch.toml`
+    /// files.
+    Config(ConfigCommand),
+
     /// Command to print the version of the `teamsearch` binary.
     Version,
 }
 
+impl Command {
+    /// Whether the command prints machine-readable output to `stdout`, in
+    /// which case nothing else should be written to `stdout`.
+    pub fn has_machine_output(&self) -> bool {
+        match self {
+            Command::Find(args) => {
+                args.output_format() != OutputFormat::Text
+                    || args.jsonl.is_some()
+                    || args.rg_json
+                    || args.vimgrep
+                    || args.format_template.is_some()
+                    || args.dry_run
+            }
+            Command::Lookup(args) => args.output_format() != OutputFormat::Text,
+            Command::Orphans(args) => args.output_format() != OutputFormat::Text,
+            Command::Suggest(args) => args.json,
+            Command::CheckStaged(_) | Command::Validate(_) => false,
+            Command::History(args) => args.json,
+            Command::Config(_) | Command::Version => false,
+        }
+    }
+}
+
 #[derive(Clone, Debug, clap::Parser)]
 pub struct FindCommand {
     /// List of files or directories to check.
@@ -53,10 +153,11 @@ pub struct FindCommand {
 
     /// Specify the path of the file of the codeowners.
     #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
-    pub codeowners: PathBuf,
+    pub codeowners: Option<PathBuf>,
 
-    /// Specify the team to check for.
-    #[clap(value_parser = parse_team_name, long, short, help = "Specify the team to check for [default: *]")]
+    /// Specify the team to check for. This can also be one of the aliases
+    /// of the configuration, which stands for all of the teams of the alias.
+    #[clap(long, short, help = "Specify the team to check for [default: *]")]
     pub teams: Vec<String>,
 
     /// Paths that should be excluded from the search.
@@ -68,9 +169,44 @@ pub struct FindCommand {
     )]
     pub exclude: Vec<String>,
 
+    /// Only search files of the given type, e.g. `rust` or `ts`. This can be
+    /// specified multiple times. Use `--type-list` to see all known types.
+    #[clap(long = "type", help_heading = "File selection", value_name = "TYPE")]
+    pub types: Vec<String>,
+
+    /// Do not search files of the given type. This can be specified
+    /// multiple times.
+    #[clap(long = "type-not", help_heading = "File selection", value_name = "TYPE")]
+    pub types_not: Vec<String>,
+
+    /// Only search files that match the given glob, relative to the root of
+    /// the search. Globs that are prefixed with `!` exclude the matching
+    /// files instead.
+    #[clap(long = "glob", short = 'g', help_heading = "File selection", value_name = "GLOB")]
+    pub globs: Vec<String>,
+
+    /// Print all of the known file types and their globs, and then exit.
+    #[clap(long, help_heading = "File selection")]
+    pub type_list: bool,
+
+    #[command(flatten)]
+    pub git: GitFilesArgs,
+
+    /// Search the files as they were at the given git revision. The files
+    /// and the CODEOWNERS file are read from the objects of the repository,
+    /// without checking the revision out.
+    #[clap(
+        long,
+        value_name = "REV",
+        conflicts_with_all = ["changed_since", "staged", "tracked_only", "write"],
+        help_heading = "File selection",
+        help = "Search the files as they were at the given git revision"
+    )]
+    pub rev: Option<String>,
+
     /// The pattern to look for within the codebase.
-    #[clap(short)]
-    pub pattern: String,
+    #[clap(short, required_unless_present = "type_list")]
+    pub pattern: Option<String>,
 
     /// Treat the pattern as case insensitive.
     #[clap(
@@ -88,10 +224,24 @@ pub struct FindCommand {
     /// [
     ///     {
     ///         "path": "some/foo/result.rs",
+    ///         "owners": ["@some-team"],
     ///         "matches": [
     ///             "start": 0,
     ///             "end": 11,
-    ///             "match": "hello world"
+    ///             "line": 1,
+    ///             "column": 1,
+    ///             "column_utf16": 1,
+    ///             "match": "hello world",
+    ///             "line_text": "hello world!",
+    ///             "captures": [
+    ///                 {
+    ///                     "index": 1,
+    ///                     "name": "greeting",
+    ///                     "start": 0,
+    ///                     "end": 5,
+    ///                     "match": "hello"
+    ///                 }
+    ///             ]
     ///         ]
     ///     }
     /// ]
@@ -99,13 +249,302 @@ pub struct FindCommand {
     #[clap(long, help = "Display the results using in JSON format")]
     pub json: bool,
 
+    /// The format in which the results are printed. `--json` is a shorthand
+    /// for `--format json`, and `--format sarif` prints a SARIF 2.1 log with a
+    /// result for each of the matches. The `csv` and `tsv` formats can only be
+    /// used together with `--count`, and print the path, owners and number of
+    /// matches of each file.
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with = "json",
+        help = "The format in which the results are printed [default: text]"
+    )]
+    pub format: Option<OutputFormat>,
+
+    /// Stream the results as JSON Lines, writing an object as soon as each
+    /// file has been searched, rather than waiting for the whole search to
+    /// finish. Each line is an object with a `type` of either `file` or
+    /// `match` (depending on the mode), and the stream ends with a `summary`
+    /// object:
+    ///
+    /// ```json
+    /// {"type":"file","path":"some/foo/result.rs","owners":["@some-team"],"matches":[...]}
+    /// {"type":"summary","files":1,"searched":12,"matches":3,"elapsed":{"secs":0,"nanos":7918375}}
+    /// ```
+    ///
+    /// The results are written in the order that the files are searched.
+    #[clap(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "file",
+        conflicts_with_all = ["json", "format", "count", "files_with_matches", "files_without_match", "write"],
+        help = "Stream the results as JSON Lines, one object per file or per match"
+    )]
+    pub jsonl: Option<JsonLinesMode>,
+
+    /// Stream the results using the same JSON Lines message protocol as
+    /// `rg --json`, i.e. `begin`, `match`, `end` and `summary` messages. The
+    /// `begin` and `match` messages additionally carry an `owners` field with
+    /// the teams that own the file.
+    #[clap(
+        long,
+        conflicts_with_all = ["json", "format", "jsonl", "count", "files_with_matches", "files_without_match", "write"],
+        help = "Stream the results using ripgrep's JSON message protocol"
+    )]
+    pub rg_json: bool,
+
+    /// Configure the colours of the results, using specs of the form
+    /// `{type}:{attribute}:{value}`, where the type is one of `path`, `line`
+    /// or `match`, the attribute is one of `fg`, `bg` or `style`. Colours
+    /// are one of `black`, `red`, `green`, `yellow`, `blue`, `magenta`,
+    /// `cyan` or `white`, and styles are one of `bold`, `underline` or
+    /// `inverted`. A spec of the form `{type}:none` clears the style of the
+    /// type. This can be specified multiple times, and the specs are applied
+    /// in order, e.g. `--colors 'match:none' --colors 'match:fg:yellow'`.
+    #[clap(long = "colors", value_name = "SPEC", help = "Configure the colours of the results")]
+    pub colours: Vec<ColourSpec>,
+
+    /// Print each match on its own line as `path:line:column:text`, which is
+    /// the format that Vim and Emacs expect for their quickfix lists. This is
+    /// a shorthand for `--format-template '{path}:{line}:{column}:{line_text}'`.
+    #[clap(
+        long,
+        conflicts_with_all = ["json", "format", "jsonl", "rg_json", "count", "files_with_matches", "files_without_match", "write", "group_by_team"],
+        help = "Print each match as `path:line:column:text`"
+    )]
+    pub vimgrep: bool,
+
+    /// Print each match on its own line using the given template. The
+    /// template can contain the following placeholders:
+    ///
+    /// - `{path}`: the path of the file.
+    /// - `{relpath}`: the path of the file, relative to the current directory.
+    /// - `{line}`: the line number of the start of the match.
+    /// - `{column}`: the column of the start of the match, in bytes.
+    /// - `{start}` and `{end}`: the byte offsets of the match within the file.
+    /// - `{match}`: the text of the match.
+    /// - `{line_text}`: the text of the line that contains the match.
+    /// - `{owners}`: the teams that own the file, separated by commas.
+    ///
+    /// Literal braces can be written as `{{` and `}}`.
+    #[clap(
+        long,
+        value_name = "TEMPLATE",
+        conflicts_with_all = ["json", "format", "jsonl", "rg_json", "count", "files_with_matches", "files_without_match", "write", "group_by_team", "vimgrep"],
+        help = "Print each match using the given template, e.g. '{path}:{line}:{owners}:{match}'"
+    )]
+    pub format_template: Option<Template>,
+
+    /// Print the teams that own each file next to the path of the file, along
+    /// with the metadata of each of the teams if there's a team registry.
+    /// The JSON output always includes the owners of each file, but not their
+    /// metadata, which is included by `lookup --json` instead.
+    #[clap(long, help = "Print the teams that own each file next to its path")]
+    pub show_owners: bool,
+
+    /// Print the results grouped under a heading for each of the teams that
+    /// own the matched files, along with the total number of matches for the
+    /// team. Files that are owned by multiple teams appear under each of the
+    /// teams.
+    #[clap(
+        long,
+        conflicts_with_all = ["json", "format", "jsonl", "rg_json", "files_with_matches", "files_without_match", "write"],
+        help = "Group the results by the teams that own the matched files"
+    )]
+    pub group_by_team: bool,
+
+    /// Sort the results in ascending order by the given key. By default,
+    /// the results are sorted by path. Sorting by `team` orders the files by
+    /// the first of their owners, and sorting by `modified` orders them by
+    /// the time they were last modified.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "KIND",
+        default_value_t,
+        help = "Sort the results in ascending order"
+    )]
+    pub sort: SortKind,
+
+    /// Sort the results in descending order by the given key.
+    #[clap(
+        long,
+        value_enum,
+        value_name = "KIND",
+        conflicts_with = "sort",
+        help = "Sort the results in descending order"
+    )]
+    pub sortr: Option<SortKind>,
+
+    /// Stop searching a file after the given number of matches.
+    #[clap(
+        short = 'm',
+        long,
+        value_name = "NUM",
+        help = "Stop searching a file after the given number of matches"
+    )]
+    pub max_count: Option<usize>,
+
+    /// Stop the whole search after the given number of matches. The files
+    /// are searched in parallel, so which of the matches are reported isn't
+    /// deterministic, but the remaining files won't be walked or searched
+    /// once the limit is hit. This is useful for checking whether a pattern
+    /// is used at all, e.g. `--max-total 1`.
+    #[clap(long, value_name = "NUM", help = "Stop the search after the given number of matches")]
+    pub max_total: Option<usize>,
+
     /// Whether to simply output the counts of the matches per file.
     #[clap(long, help = "Output the counts of the matches per file")]
     pub count: bool,
+
+    /// Only print the paths of the files that contain at least one match.
+    /// The search of a file stops as soon as the first match is found.
+    #[clap(
+        short = 'l',
+        long,
+        help = "Only print the paths of files with at least one match",
+        conflicts_with_all = ["files_without_match", "count"]
+    )]
+    pub files_with_matches: bool,
+
+    /// Only print the paths of the files that contain no matches.
+    #[clap(
+        short = 'L',
+        long,
+        help = "Only print the paths of files without any matches",
+        conflicts_with_all = ["files_with_matches", "count"]
+    )]
+    pub files_without_match: bool,
+
+    /// Report the lines that do not match the pattern, rather than the
+    /// matches themselves.
+    #[clap(short = 'v', long, help = "Report the lines that do not match the pattern")]
+    pub invert_match: bool,
+
+    /// Replace every match with the given template when printing the
+    /// results. Capture groups of the pattern can be referenced using `$1`
+    /// or `${name}`, and a literal `$` can be written as `$$`.
+    #[clap(
+        short = 'r',
+        long,
+        value_name = "TEMPLATE",
+        help = "Replace every match with the given template",
+        conflicts_with_all = ["invert_match", "files_with_matches", "files_without_match", "count"]
+    )]
+    pub replace: Option<String>,
+
+    /// Apply the replacements in place to each of the matched files. Only
+    /// files that are owned by the selected teams are modified, so at least
+    /// one team must be given.
+    #[clap(
+        long,
+        requires_all = ["replace", "teams"],
+        help = "Apply the replacements to the matched files"
+    )]
+    pub write: bool,
+
+    /// Print a unified diff of the changes that `--write` would make,
+    /// without modifying any of the files.
+    #[clap(
+        long,
+        requires = "write",
+        conflicts_with = "json",
+        help = "Print the changes that `--write` would make as a unified diff"
+    )]
+    pub dry_run: bool,
+}
+
+impl FindCommand {
+    /// The format in which the results should be printed, taking `--json`
+    /// into account.
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json { OutputFormat::Json } else { self.format.unwrap_or_default() }
+    }
+}
+
+/// Arguments that restrict a command to the files that are tracked by, or
+/// have changed in, the git repository of the CODEOWNERS file.
+#[derive(Clone, Debug, Default, clap::Args)]
+pub struct GitFilesArgs {
+    /// Only consider the files that have been added or modified since the
+    /// given git revision, including uncommitted changes and files that
+    /// aren't tracked yet.
+    #[arg(
+        long,
+        value_name = "REV",
+        conflicts_with = "staged",
+        help_heading = "File selection",
+        help = "Only consider the files that have changed since the given git revision"
+    )]
+    pub changed_since: Option<String>,
+
+    /// Only consider the files that have been added or modified by the
+    /// changes that are staged in git.
+    #[arg(
+        long,
+        help_heading = "File selection",
+        help = "Only consider the files that have staged changes"
+    )]
+    pub staged: bool,
+
+    /// Only consider the files that are tracked by git, which are read from
+    /// the index of the repository. Unlike `.gitignore`, this also skips any
+    /// untracked files that aren't ignored.
+    #[arg(
+        long,
+        help_heading = "File selection",
+        help = "Only consider the files that are tracked by git"
+    )]
+    pub tracked_only: bool,
+}
+
+/// The format in which the results of a command are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+
+    /// A JSON document.
+    Json,
+
+    /// A SARIF 2.1 log, for use with code scanning tools.
+    Sarif,
+
+    /// Comma separated values, with a header row.
+    Csv,
+
+    /// Tab separated values, with a header row.
+    Tsv,
 }
 
-fn parse_team_name(raw_team: &str) -> Result<String, String> {
-    if raw_team.starts_with('@') { Ok(raw_team.to_string()) } else { Ok(format!("@{}", raw_team)) }
+/// The key that the results of `find` are sorted by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKind {
+    /// Sort by the path of the file.
+    #[default]
+    Path,
+
+    /// Sort by the number of matches in the file.
+    Matches,
+
+    /// Sort by the first team that owns the file.
+    Team,
+
+    /// Sort by the time the file was last modified.
+    Modified,
+}
+
+/// What each of the objects emitted by `--jsonl` describes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum JsonLinesMode {
+    /// Emit one object per file, with all of the matches in the file.
+    File,
+
+    /// Emit one object per match.
+    Match,
 }
 
 #[derive(Clone, Debug, clap::Parser)]
@@ -116,7 +555,21 @@ pub struct LookupCommand {
 
     /// Specify the path of the file of the codeowners.
     #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
-    pub codeowners: PathBuf,
+    pub codeowners: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub git: GitFilesArgs,
+
+    /// Evaluate the ownership of the files as they were at the given git
+    /// revision. The CODEOWNERS file and the files are read from the tree
+    /// of the revision, without checking it out.
+    #[clap(
+        long,
+        value_name = "REV",
+        conflicts_with_all = ["changed_since", "staged", "tracked_only"],
+        help = "Evaluate the ownership of the files at the given git revision"
+    )]
+    pub rev: Option<String>,
 
     /// Display the results using a JSON format. We output the contents
     /// of the search in the following format:
@@ -125,16 +578,38 @@ pub struct LookupCommand {
     /// [
     ///     {
     ///         "path": "some/foo/result.rs",
-    ///         "team": "@some-team"
+    ///         "teams": ["@some-team"],
+    ///         "rules": ["/some/foo/"]
     ///     },
     ///     {
     ///         "path": "some/bar/result.rs",
-    ///         "team": null
+    ///         "teams": [],
+    ///         "rules": []
     ///     },
     /// ]
     /// ```
     #[clap(long, help = "Display the results using in JSON format")]
     pub json: bool,
+
+    /// The format in which the results are printed. `--json` is a shorthand
+    /// for `--format json`, and the `csv` and `tsv` formats print a row with
+    /// the path, owner and the CODEOWNERS rule for each of the owners of a
+    /// file.
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with = "json",
+        help = "The format in which the results are printed [default: text]"
+    )]
+    pub format: Option<OutputFormat>,
+}
+
+impl LookupCommand {
+    /// The format in which the results should be printed, taking `--json`
+    /// into account.
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json { OutputFormat::Json } else { self.format.unwrap_or_default() }
+    }
 }
 
 #[derive(Clone, Debug, clap::Parser)]
@@ -145,7 +620,21 @@ pub struct OrphanCommand {
 
     /// Specify the path of the file of the codeowners.
     #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
-    pub codeowners: PathBuf,
+    pub codeowners: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub git: GitFilesArgs,
+
+    /// Evaluate the ownership of the files as they were at the given git
+    /// revision. The CODEOWNERS file and the files are read from the tree
+    /// of the revision, without checking it out.
+    #[clap(
+        long,
+        value_name = "REV",
+        conflicts_with_all = ["changed_since", "staged", "tracked_only"],
+        help = "Evaluate the ownership of the files at the given git revision"
+    )]
+    pub rev: Option<String>,
 
     /// Paths that should be excluded from the search.
     #[clap(
@@ -167,4 +656,145 @@ pub struct OrphanCommand {
     /// ```
     #[clap(long, help = "Display the results using in JSON format")]
     pub json: bool,
+
+    /// The format in which the results are printed. `--json` is a shorthand
+    /// for `--format json`, `--format sarif` prints a SARIF 2.1 log with a
+    /// result for each of the orphaned files, and the `csv` and `tsv` formats
+    /// print the path, size and extension of each of the orphaned files.
+    #[clap(
+        long,
+        value_enum,
+        conflicts_with = "json",
+        help = "The format in which the results are printed [default: text]"
+    )]
+    pub format: Option<OutputFormat>,
+}
+
+impl OrphanCommand {
+    /// The format in which the results should be printed, taking `--json`
+    /// into account.
+    pub fn output_format(&self) -> OutputFormat {
+        if self.json { OutputFormat::Json } else { self.format.unwrap_or_default() }
+    }
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct SuggestCommand {
+    /// List of directories that should be checked for orphans.
+    #[clap(help = "List of files or directories to check [default: .]")]
+    pub files: Vec<PathBuf>,
+
+    /// Specify the path of the file of the codeowners.
+    #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
+    pub codeowners: Option<PathBuf>,
+
+    /// The roster that maps the authors of the commits to their teams. This
+    /// is a JSON object with the members of each team, where each member is
+    /// either their email address or their name:
+    ///
+    /// ```json
+    /// {
+    ///     "@org/core": ["alice@example.com", "Bob Smith"],
+    ///     "@org/web": ["carol@example.com"]
+    /// }
+    /// ```
+    #[clap(long, short, value_name = "PATH", help = "The roster that maps authors to their teams")]
+    pub roster: PathBuf,
+
+    /// Only count the commits that are more recent than the given date, which
+    /// can be any date that `git log --since` accepts.
+    #[clap(
+        long,
+        value_name = "DATE",
+        default_value = "1 year ago",
+        help = "Only count the commits that are more recent than the given date"
+    )]
+    pub since: String,
+
+    /// Paths that should be excluded from the search.
+    #[clap(
+        long,
+        short,
+        help = "Paths that should be excluded from the search [default: none]",
+        value_name = "PATH"
+    )]
+    pub exclude: Vec<String>,
+
+    /// Display the results using a JSON format. We output the contents
+    /// of the search in the following format:
+    ///
+    /// ```json
+    /// [
+    ///     {
+    ///         "directory": "some/foo",
+    ///         "files": ["some/foo/orphan.rs"],
+    ///         "commits": 4,
+    ///         "candidates": [
+    ///             { "team": "@some-team", "commits": 3, "share": 0.75 }
+    ///         ],
+    ///         "rules": ["/some/foo/* @some-team"]
+    ///     }
+    /// ]
+    /// ```
+    #[clap(long, help = "Display the results using in JSON format")]
+    pub json: bool,
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct CheckStagedCommand {
+    /// Specify the path of the file of the codeowners. If the CODEOWNERS file
+    /// has staged changes, the staged version of it is used.
+    #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
+    pub codeowners: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct HistoryCommand {
+    /// The path to show the ownership history of.
+    #[clap(help = "The file or directory to show the ownership history of")]
+    pub path: PathBuf,
+
+    /// Specify the path of the file of the codeowners.
+    #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
+    pub codeowners: Option<PathBuf>,
+
+    /// Display the results using a JSON format. We output each of the commits
+    /// that changed the owners of the path, from the oldest to the newest, in
+    /// the following format:
+    ///
+    /// ```json
+    /// [
+    ///     {
+    ///         "commit": "4176bf7...",
+    ///         "date": "2024-03-01",
+    ///         "author": "Some One",
+    ///         "summary": "Hand over the docs",
+    ///         "before": { "teams": ["@some-team"], "rules": ["/docs/"] },
+    ///         "after": { "teams": [], "rules": [] }
+    ///     }
+    /// ]
+    /// ```
+    #[clap(long, help = "Display the results using in JSON format")]
+    pub json: bool,
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct ValidateCommand {
+    /// Specify the path of the file of the codeowners.
+    #[clap(long, short, help = "Specify the path of the CODEOWNERS file [default: CODEOWNERS]")]
+    pub codeowners: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, clap::Parser)]
+pub struct ConfigCommand {
+    #[command(subcommand)]
+    pub command: ConfigSubcommand,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+pub enum ConfigSubcommand {
+    /// Print the configuration that results from merging the user-level and
+    /// project `teamsearch.toml` files with the command line, along with
+    /// where each of the values came from.
+    Show,
 }
@@ -0,0 +1,161 @@
+//! The colours that are used to highlight the results of `find`. These can be
+//! configured with specs in the same style as ripgrep's `--colors`, e.g.
+//! `match:fg:yellow`, `path:style:underline` or `line:none`.
+
+use std::str::FromStr;
+
+use teamsearch_utils::highlight::{Colour, Modifier, Style};
+
+/// The kind of text that a [ColourSpec] applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourTarget {
+    /// The path of a file.
+    Path,
+
+    /// The number of a line.
+    Line,
+
+    /// The text of a match.
+    Match,
+}
+
+/// The change that a [ColourSpec] makes to the style of its target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColourChange {
+    /// Set the colour of the text.
+    Foreground(Colour),
+
+    /// Set the colour of the background.
+    Background(Colour),
+
+    /// Add a modifier to the text.
+    Modifier(Modifier),
+
+    /// Clear the whole style, so the text isn't highlighted.
+    None,
+}
+
+/// A single spec of the form `{type}:{attribute}:{value}` or `{type}:none`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColourSpec {
+    pub target: ColourTarget,
+    pub change: ColourChange,
+}
+
+impl FromStr for ColourSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts = s.split(':').collect::<Vec<_>>();
+
+        let target = match parts[0] {
+            "path" => ColourTarget::Path,
+            "line" => ColourTarget::Line,
+            "match" => ColourTarget::Match,
+            other => {
+                return Err(format!(
+                    "unrecognized type `{other}`, expected `path`, `line` or `match`"
+                ));
+            }
+        };
+
+        let change = match parts[1..] {
+            ["none"] => ColourChange::None,
+            ["fg", colour] => ColourChange::Foreground(colour.parse()?),
+            ["bg", colour] => ColourChange::Background(colour.parse()?),
+            ["style", modifier] => ColourChange::Modifier(modifier.parse()?),
+            _ => {
+                return Err(format!(
+                    "invalid spec `{s}`, expected `{{type}}:{{fg|bg|style}}:{{value}}` or `{{type}}:none`"
+                ));
+            }
+        };
+
+        Ok(ColourSpec { target, change })
+    }
+}
+
+/// The styles that are used for each kind of text.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    pub path: Style,
+    pub line: Style,
+    pub matched: Style,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            path: Colour::Magenta.into(),
+            line: Colour::Green.into(),
+            matched: Colour::Red.into(),
+        }
+    }
+}
+
+impl Palette {
+    /// Create a palette by applying the given specs, in order, on top of the
+    /// default palette.
+    pub fn new(specs: &[ColourSpec]) -> Self {
+        let mut palette = Palette::default();
+
+        for spec in specs {
+            let style = match spec.target {
+                ColourTarget::Path => &mut palette.path,
+                ColourTarget::Line => &mut palette.line,
+                ColourTarget::Match => &mut palette.matched,
+            };
+
+            match spec.change {
+                ColourChange::Foreground(colour) => style.foreground = Some(colour),
+                ColourChange::Background(colour) => style.background = Some(colour),
+                ColourChange::Modifier(modifier) => style.modifiers.push(modifier),
+                ColourChange::None => *style = Style::default(),
+            }
+        }
+
+        palette
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        assert_eq!(
+            "match:fg:yellow".parse(),
+            Ok(ColourSpec {
+                target: ColourTarget::Match,
+                change: ColourChange::Foreground(Colour::Yellow)
+            })
+        );
+        assert_eq!(
+            "path:none".parse(),
+            Ok(ColourSpec { target: ColourTarget::Path, change: ColourChange::None })
+        );
+
+        assert!("owner:fg:red".parse::<ColourSpec>().is_err());
+        assert!("line:fg:purple".parse::<ColourSpec>().is_err());
+        assert!("line:fg".parse::<ColourSpec>().is_err());
+    }
+
+    #[test]
+    fn test_palette() {
+        let specs = ["path:none", "path:style:bold", "match:bg:blue", "match:fg:white"]
+            .map(|spec| spec.parse().unwrap());
+        let palette = Palette::new(&specs);
+
+        assert_eq!(palette.path, Style { modifiers: vec![Modifier::Bold], ..Style::default() });
+        assert_eq!(
+            palette.matched,
+            Style {
+                foreground: Some(Colour::White),
+                background: Some(Colour::Blue),
+                ..Style::default()
+            }
+        );
+        assert_eq!(palette.line, Colour::Green.into());
+    }
+}
@@ -0,0 +1,139 @@
+//! Implementation of the `check-staged` command, which checks that all of the
+//! files that are added by the staged changes are owned by a team. This is
+//! meant to be run from a git hook, so only the staged files are looked at
+//! rather than the whole tree.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use teamsearch_utils::fs;
+use teamsearch_workspace::{
+    codeowners::{CodeOwners, escape_path},
+    git::{self, Changes},
+};
+
+/// A staged file that isn't owned by any team.
+pub(crate) struct Unowned {
+    /// The path of the file.
+    pub(crate) path: PathBuf,
+
+    /// A CODEOWNERS rule that would assign the file to a team, if one could
+    /// be suggested.
+    pub(crate) rule: Option<String>,
+}
+
+/// The result of checking the staged files.
+#[derive(Default)]
+pub(crate) struct CheckResult {
+    /// The number of staged files that were checked.
+    pub(crate) checked: usize,
+
+    /// The staged files that aren't owned by any team.
+    pub(crate) unowned: Vec<Unowned>,
+}
+
+pub fn check_staged(codeowners: &Path) -> Result<CheckResult> {
+    let codeowners = fs::normalize_path(codeowners);
+    let dir = codeowners.parent().unwrap_or(Path::new("."));
+    let root = git::repository_root(dir)?;
+
+    // The CODEOWNERS file might be changed by the same commit, in which case
+    // the staged version of it is the one that counts.
+    let codeowners = match git::read_staged(&root, &codeowners)? {
+        Some(contents) => CodeOwners::parse(&contents, &root)?,
+        None => CodeOwners::parse_from_file(&codeowners, &root)?,
+    };
+
+    let files = git::changed_files(&root, Changes::Added)?;
+    let unowned = files
+        .iter()
+        .filter(|file| !codeowners.is_owned(file))
+        .map(|file| Unowned { path: file.clone(), rule: suggest_rule(&root, file, &codeowners) })
+        .collect();
+
+    Ok(CheckResult { checked: files.len(), unowned })
+}
+
+/// Suggest a CODEOWNERS rule for an unowned `file`, which assigns it to the
+/// teams that own the most of the files and directories next to it. If none
+/// of them are owned, the closest parent directory that has owned entries is
+/// used instead.
+fn suggest_rule(root: &Path, file: &Path, codeowners: &CodeOwners) -> Option<String> {
+    let relative = file.strip_prefix(root).ok()?;
+
+    for dir in file.ancestors().skip(1).take_while(|dir| dir.starts_with(root)) {
+        let Ok(entries) = std::fs::read_dir(dir) else { continue };
+
+        let mut counts: HashMap<Vec<String>, usize> = HashMap::new();
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let path = entry.path();
+            if path == file {
+                continue;
+            }
+
+            let owners = codeowners.lookup(&path);
+            if !owners.is_empty() {
+                *counts.entry(owners).or_default() += 1;
+            }
+        }
+
+        // Ties are broken by the names of the teams, so that the suggestion
+        // is stable.
+        let owners = counts.into_iter().max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)));
+        if let Some((owners, _)) = owners {
+            let path = escape_path(&relative.to_string_lossy());
+            return Some(format!("/{path} {}", owners.join(" ")));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use teamsearch_workspace::testing::{git, repository, write};
+
+    use super::*;
+
+    #[test]
+    fn test_check_staged() {
+        let repo = repository(&[
+            ("CODEOWNERS", "/src/ @org/core\n"),
+            ("src/main.rs", ""),
+            ("docs/old.md", ""),
+            ("docs/index.md", ""),
+        ]);
+        let root = repo.path();
+
+        // The staged CODEOWNERS file counts, rather than the committed one.
+        write(
+            root,
+            &[
+                ("CODEOWNERS", "/src/ @org/core\n/docs/index.md @org/docs\n"),
+                ("src/lib.rs", ""),
+                ("docs/dir with space/a.md", ""),
+                ("untracked.rs", ""),
+            ],
+        );
+        git(root, &["add", "CODEOWNERS", "src/lib.rs", "docs/dir with space/a.md"]);
+        git(root, &["mv", "docs/old.md", "docs/new.md"]);
+
+        let result = check_staged(&root.join("CODEOWNERS")).unwrap();
+        assert_eq!(result.checked, 3);
+
+        let unowned = result.unowned.iter().map(|file| (&file.path, file.rule.as_deref()));
+        assert_eq!(
+            unowned.collect::<Vec<_>>(),
+            [
+                (
+                    &root.join("docs/dir with space/a.md"),
+                    Some(r"/docs/dir\ with\ space/a.md @org/docs")
+                ),
+                (&root.join("docs/new.md"), Some("/docs/new.md @org/docs")),
+            ]
+        );
+    }
+}
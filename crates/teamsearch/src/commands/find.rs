@@ -1,35 +1,152 @@
 //! Implementation of the `find` command.
 
-use std::{iter::once, path::PathBuf};
+use std::{
+    iter::once,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 use anyhow::Result;
-use derive_more::Constructor;
 use itertools::Itertools;
-use log::debug;
-use rayon::prelude::*;
-use teamsearch_matcher::{FileMatches, Pattern, search_file};
+use log::{debug, warn};
+use teamsearch_matcher::{FileMatches, Pattern, SearchOptions, search_bytes, search_file};
 use teamsearch_utils::{fs, timed};
 use teamsearch_workspace::{
-    codeowners::CodeOwners,
-    resolver::find_files_in_paths,
+    codeowners::{CodeOwners, CodeOwnersTree},
+    resolver::{ResolvedFile, visit_files_in_paths},
     settings::{FilePattern, Settings},
 };
 
+use crate::cli::SortKind;
+
+/// The options that control a search.
+pub(crate) struct FindOptions<'a> {
+    /// The teams that the search should be scoped to. If none of the teams are
+    /// present in any of the `CODEOWNERS` files, the whole repository is
+    /// searched.
+    pub teams: Vec<String>,
+
+    /// Paths that should be excluded from the search.
+    pub exclusions: Vec<String>,
+
+    /// The pattern to search for.
+    pub pattern: Pattern<'a>,
+
+    /// Options that control how each of the files is searched.
+    pub search: SearchOptions<'a>,
+
+    /// Whether files without any matches should be reported too.
+    pub keep_empty: bool,
+
+    /// The maximum number of matches across all of the files. Once this many
+    /// matches have been found, the search stops without looking at the rest
+    /// of the files.
+    pub max_total: Option<usize>,
+
+    /// How the results of [find] are ordered, this doesn't apply to
+    /// [find_each].
+    pub sort: Sort,
+}
+
+/// How the results of a search are ordered.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Sort {
+    /// The key that the results are ordered by.
+    pub kind: SortKind,
+
+    /// Whether the results are in descending order.
+    pub reverse: bool,
+}
+
 /// The result of a search.
-#[derive(Default, Constructor)]
+#[derive(Default)]
 pub(crate) struct FindResult {
     /// The items that we're found within the files.
     pub file_matches: Vec<FileMatches>,
+
+    /// The number of files that were searched.
+    pub searched: usize,
+
+    /// The teams that the search was scoped to, this only includes teams
+    /// that are present in any of the `CODEOWNERS` files.
+    pub teams: Vec<String>,
+
+    /// The `CODEOWNERS` files that were used for the search, including the
+    /// files of any nested repositories.
+    pub codeowners: CodeOwnersTree,
 }
 
+impl FindResult {
+    /// Check whether the given path is owned by any of the teams that the
+    /// search was scoped to. If the search wasn't scoped to any teams, then
+    /// every path is within scope.
+    pub fn is_in_scope(&self, path: &Path) -> bool {
+        let file = self.codeowners.resolve(path);
+        self.teams.is_empty()
+            || self.teams.iter().any(|team| file.codeowners.is_owned_by(path, team))
+    }
+}
+
+/// Search the given files, and collect all of the results ordered as specified
+/// by the `sort` of the options.
 pub(crate) fn find(
+    files: &[PathBuf],
+    settings: Settings,
+    options: FindOptions<'_>,
+) -> Result<FindResult> {
+    let sort = options.sort;
+    let matches = Mutex::new(Vec::new());
+    let mut result = find_each(files, settings, options, |file| {
+        matches.lock().unwrap().push(file);
+        Ok(())
+    })?;
+
+    // The matches of each file will already be ordered by the line number, so
+    // we only need to order the files. Ties are always broken by the path of
+    // the file, so that the order is stable.
+    let mut file_matches = matches.into_inner().unwrap();
+    match sort.kind {
+        SortKind::Path => file_matches.sort_by(|a, b| a.path.cmp(&b.path)),
+        SortKind::Matches => {
+            file_matches.sort_by(|a, b| a.len().cmp(&b.len()).then(a.path.cmp(&b.path)))
+        }
+        // Files are ordered by the first of their owners, with the files that
+        // aren't owned by anyone last.
+        SortKind::Team => file_matches.sort_by(|a, b| {
+            let key = |file: &FileMatches| (file.owners.is_empty(), file.owners.first().cloned());
+            key(a).cmp(&key(b)).then(a.path.cmp(&b.path))
+        }),
+        SortKind::Modified => file_matches.sort_by_cached_key(|file| {
+            let modified = std::fs::metadata(&file.path).and_then(|m| m.modified()).ok();
+            (modified, file.path.clone())
+        }),
+    }
+
+    if sort.reverse {
+        file_matches.reverse();
+    }
+
+    result.file_matches = file_matches;
+    Ok(result)
+}
+
+/// Search the given files, and pass the results of each file to `on_file` as
+/// soon as the file has been searched, with the `owners` of the file filled in.
+/// The results are reported in no particular order, and the `file_matches` of
+/// the returned [FindResult] are always empty.
+pub(crate) fn find_each(
     files: &[PathBuf],
     mut settings: Settings,
-    team: Vec<String>,
-    exclusions: Vec<String>,
-    pattern: String,
-    case_sensitive: bool,
+    options: FindOptions<'_>,
+    on_file: impl Fn(FileMatches) -> Result<()> + Sync,
 ) -> Result<FindResult> {
+    let FindOptions { teams: team, exclusions, pattern, search, keep_empty, max_total, .. } =
+        options;
+
     let paths: Vec<PathBuf> =
         files.iter().chain(once(&settings.codeowners)).map(fs::normalize_path).unique().collect();
 
@@ -41,54 +158,275 @@ pub(crate) fn find(
 
     // We've gotta parse in the `CODEOWNERS` file, and then
     // extract the given patterns that are specified for the particular team.
-    let codeowners = CodeOwners::parse_from_file(&settings.codeowners, &root)?;
-    let teams = team.iter().filter(|t| codeowners.has_team(t)).unique().collect::<Vec<_>>();
+    let codeowners = CodeOwnersTree::new(
+        fs::normalize_path(&settings.codeowners),
+        CodeOwners::parse_from_settings(&settings, &root)?,
+    );
 
     // If we get no teams at all, we assume that we're doing a wide scan
     // across an entire repo. This is useful for other modes of scanning that
     // are looking for things across all teams, or for a specific pattern.
-    if teams.is_empty() {
-        settings.file_resolver.include =
-            settings.file_resolver.include.extend(vec![FilePattern::all()])?;
-    }
-
-    for team in &teams {
-        let patterns = codeowners.get_patterns_for_team(team).to_vec();
-        settings.file_resolver.include = settings.file_resolver.include.extend(patterns)?;
-    }
+    //
+    // Otherwise, the files are scoped to the teams as they're found, since
+    // the files of nested repositories are governed by their own CODEOWNERS.
+    settings.file_resolver.include =
+        settings.file_resolver.include.extend(vec![FilePattern::all()])?;
 
     // Collect all of the paths that should be excluded.
-    let mut all_exclusions = codeowners.get_ignored_patterns().to_vec();
+    let mut all_exclusions = codeowners.outer().get_ignored_patterns().to_vec();
     all_exclusions.extend(exclusions.iter().map(FilePattern::new_user));
 
     settings.file_resolver.user_exclude =
         settings.file_resolver.user_exclude.extend(all_exclusions)?;
 
-    // Firstly, we need to discover all of the files in the provided paths.
-    let files = timed(
-        || find_files_in_paths(&root, files, &settings),
+    let mut teams = team.into_iter().unique().collect::<Vec<_>>();
+
+    // The teams might only be present in the CODEOWNERS files of nested
+    // repositories, which are otherwise only found during the walk, so they're
+    // found up front when none of the teams are in the outer file. The files
+    // of a git revision don't have any nested repositories. If none of the
+    // teams are present in any of the files, nothing would be in scope, so the
+    // whole repository is searched instead.
+    if !teams.is_empty() && !teams.iter().any(|team| codeowners.outer().has_team(team)) {
+        if settings.revision.is_none() {
+            let discover = |_| ControlFlow::Continue(());
+            visit_files_in_paths(&root, files, &settings, Some(&codeowners), discover)?;
+        }
+
+        if !teams.iter().any(|team| codeowners.has_team(team)) {
+            warn!(
+                "None of the teams are in the CODEOWNERS files, searching all of the files instead."
+            );
+            teams.clear();
+        }
+    }
+
+    let searched = AtomicUsize::new(0);
+    let total = AtomicUsize::new(0);
+    let error = Mutex::new(None);
+
+    // Take as many of the `matches` as are left of the total, returning `None`
+    // once the total has been reached.
+    let take_total = |mut matches: FileMatches| {
+        if let Some(max) = max_total {
+            let found = total.fetch_add(matches.len(), Ordering::Relaxed);
+            if found >= max {
+                return None;
+            }
+
+            matches.truncate(max - found);
+        }
+
+        Some(matches)
+    };
+
+    let emit = |mut matches: FileMatches, codeowners: &CodeOwners| {
+        matches.owners = codeowners.lookup(&matches.path);
+        match on_file(matches) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(err) => {
+                error.lock().unwrap().get_or_insert(err);
+                ControlFlow::Break(())
+            }
+        }
+    };
+
+    // Each of the files is searched as soon as the walker finds it, so that
+    // the walk can stop as soon as the limit on the number of matches is hit.
+    let visit = |entry: Result<ResolvedFile, _>| {
+        if max_total.is_some_and(|max| total.load(Ordering::Relaxed) >= max) {
+            return ControlFlow::Break(());
+        }
+
+        let Ok(entry) = entry else {
+            return ControlFlow::Continue(());
+        };
+
+        // Files that aren't owned by anyone in particular are excluded, which
+        // is done here for the CODEOWNERS files of nested repositories.
+        let file = codeowners.resolve(entry.path());
+        if file.codeowners.is_ignored(entry.path()) {
+            return ControlFlow::Continue(());
+        }
+
+        if !teams.is_empty()
+            && !teams.iter().any(|team| file.codeowners.is_owned_by(entry.path(), team))
+        {
+            return ControlFlow::Continue(());
+        }
+
+        // The contents of the files of a git revision are read from the
+        // objects of the repository instead of the working tree.
+        searched.fetch_add(1, Ordering::Relaxed);
+        let path = entry.into_path();
+        let result = match &settings.revision {
+            Some(revision) => revision
+                .read(&path)
+                .and_then(|contents| search_bytes(pattern, search, path, contents)),
+            None => search_file(pattern, search, path),
+        };
+
+        let Ok(matches) = result else {
+            return ControlFlow::Continue(());
+        };
+
+        // Files without any matches are only interesting when the caller
+        // explicitly asked for them, i.e. `--files-without-match`.
+        if !keep_empty && matches.is_empty() {
+            return ControlFlow::Continue(());
+        }
+
+        // Other files may have been searched at the same time, so only keep
+        // as many of the matches as are left.
+        match take_total(matches) {
+            Some(matches) => emit(matches, &file.codeowners),
+            None => ControlFlow::Break(()),
+        }
+    };
+
+    timed(
+        || visit_files_in_paths(&root, files, &settings, Some(&codeowners), visit),
         log::Level::Debug,
-        |duration, result| {
-            debug!("resolved {} files in {:?}", result.as_ref().map_or(0, |f| f.len()), duration)
+        |duration, _| {
+            debug!("searched {} files in {:?}", searched.load(Ordering::Relaxed), duration)
         },
     )?;
 
-    let mut matches: Vec<_> = files
-        .into_par_iter()
-        .map(|entry| -> Result<_, _> {
-            search_file(Pattern::new(&pattern, case_sensitive), entry?.into_path())
-        })
-        .filter(|result| {
-            if let Ok(matches) = result {
-                return !matches.is_empty();
-            }
+    if let Some(err) = error.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    teams.retain(|team| codeowners.has_team(team));
+    Ok(FindResult { file_matches: vec![], searched: searched.into_inner(), teams, codeowners })
+}
+
+#[cfg(test)]
+mod tests {
+    use teamsearch_workspace::testing::{git, repository, write};
+
+    use super::*;
+
+    /// Search the files of `root` for `foo`, with the options changed by
+    /// `configure`.
+    fn search(root: &Path, configure: impl FnOnce(&mut FindOptions<'_>)) -> FindResult {
+        let mut settings = Settings::new(true, root.join("CODEOWNERS"));
+        settings.threads = Some(4);
+        let mut options = FindOptions {
+            teams: vec![],
+            exclusions: vec![],
+            pattern: Pattern::new("foo", false),
+            search: SearchOptions::default(),
+            keep_empty: false,
+            max_total: None,
+            sort: Sort::default(),
+        };
 
-            false
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+        configure(&mut options);
+        find(&[root.to_path_buf()], settings, options).unwrap()
+    }
+
+    /// Search the files of `root` for `foo`, scoped to the given `teams`.
+    fn find_foo(root: &Path, teams: &[&str]) -> FindResult {
+        search(root, |options| options.teams = teams.iter().map(|team| team.to_string()).collect())
+    }
+
+    fn paths(root: &Path, result: &FindResult) -> Vec<String> {
+        let paths = result.file_matches.iter().map(|file| file.path.strip_prefix(root).unwrap());
+        paths.map(|path| path.display().to_string()).collect()
+    }
+
+    #[test]
+    fn test_find_nested_teams() {
+        let repo = repository(&[
+            ("CODEOWNERS", "/src/ @org/core\n"),
+            ("src/a.rs", "foo"),
+            ("other.rs", "foo"),
+        ]);
+        let root = repo.path();
+
+        write(
+            root,
+            &[
+                ("nested/CODEOWNERS", "/lib/ @org/nested\n"),
+                ("nested/lib/b.rs", "foo"),
+                ("nested/c.rs", "foo"),
+            ],
+        );
+        git(&root.join("nested"), &["init", "-q"]);
+
+        // The team is only in the CODEOWNERS file of the nested repository,
+        // whose rules are relative to the nested repository.
+        let result = find_foo(root, &["@org/nested"]);
+        assert_eq!(paths(root, &result), ["nested/lib/b.rs"]);
+        assert_eq!(result.teams, ["@org/nested"]);
+        assert_eq!(result.file_matches[0].owners, ["@org/nested"]);
+
+        // None of the teams exist, so every file is searched.
+        let result = find_foo(root, &["@org/missing"]);
+        assert_eq!(
+            paths(root, &result),
+            ["nested/c.rs", "nested/lib/b.rs", "other.rs", "src/a.rs"]
+        );
+        assert!(result.teams.is_empty());
+    }
 
-    // We want to order the results by the "path" of the file, the match
-    // contents will already be ordered by the line number.
-    matches.sort_by(|a, b| a.path.cmp(&b.path));
-    Ok(FindResult::new(matches))
+    #[test]
+    fn test_find_sort() {
+        let repo = repository(&[
+            ("CODEOWNERS", "/a.rs @org/web\n/c.rs @org/core\n/d.rs @org/core @org/web\n"),
+            ("a.rs", "foo"),
+            ("b.rs", "foo foo foo"),
+            ("c.rs", "foo foo"),
+            ("d.rs", "foo foo"),
+        ]);
+        let root = repo.path();
+
+        // The files are modified in the reverse order of their paths.
+        let now = std::time::SystemTime::now();
+        for (i, path) in ["d.rs", "c.rs", "b.rs", "a.rs"].iter().enumerate() {
+            let file = std::fs::File::options().write(true).open(root.join(path)).unwrap();
+            file.set_modified(now - std::time::Duration::from_secs(60 * (4 - i as u64))).unwrap();
+        }
+
+        let sorted = |kind, reverse| {
+            let result = search(root, |options| options.sort = Sort { kind, reverse });
+            paths(root, &result)
+        };
+
+        // The files are ordered by the first of their owners, as the owners
+        // appear in the CODEOWNERS file, so `d.rs` belongs with `@org/web`.
+        // Ties are broken by the path, so the order is the same every time,
+        // regardless of the order that the files were searched in.
+        for _ in 0..3 {
+            assert_eq!(sorted(SortKind::Path, false), ["a.rs", "b.rs", "c.rs", "d.rs"]);
+            assert_eq!(sorted(SortKind::Matches, false), ["a.rs", "c.rs", "d.rs", "b.rs"]);
+            assert_eq!(sorted(SortKind::Team, false), ["c.rs", "a.rs", "d.rs", "b.rs"]);
+            assert_eq!(sorted(SortKind::Modified, false), ["d.rs", "c.rs", "b.rs", "a.rs"]);
+
+            assert_eq!(sorted(SortKind::Path, true), ["d.rs", "c.rs", "b.rs", "a.rs"]);
+            assert_eq!(sorted(SortKind::Matches, true), ["b.rs", "d.rs", "c.rs", "a.rs"]);
+            assert_eq!(sorted(SortKind::Team, true), ["b.rs", "d.rs", "a.rs", "c.rs"]);
+            assert_eq!(sorted(SortKind::Modified, true), ["a.rs", "b.rs", "c.rs", "d.rs"]);
+        }
+    }
+
+    #[test]
+    fn test_find_max_total() {
+        let files = (0..40).map(|i| (format!("src/{i}.rs"), "foo\nfoo foo\n")).collect_vec();
+        let mut files =
+            files.iter().map(|(path, contents)| (path.as_str(), *contents)).collect_vec();
+        files.push(("CODEOWNERS", "/src/ @org/core\n"));
+        let repo = repository(&files);
+        let root = repo.path();
+
+        // The files are searched concurrently, but the matches never go over
+        // the total across all of them.
+        for max in [1, 2, 5, 50] {
+            for _ in 0..5 {
+                let result = search(root, |options| options.max_total = Some(max));
+                let total = result.file_matches.iter().map(FileMatches::len).sum::<usize>();
+                assert_eq!(total, max);
+            }
+        }
+    }
 }
@@ -0,0 +1,134 @@
+//! Implementation of the `history` command, which shows how the ownership of
+//! a path changed over the history of the CODEOWNERS file.
+
+use std::path::Path;
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+use teamsearch_utils::fs;
+use teamsearch_workspace::{codeowners::CodeOwners, git};
+
+/// The owners of a path at a point in the history.
+#[derive(Serialize, Default, Clone, PartialEq)]
+pub(crate) struct Ownership {
+    /// The teams that own the path.
+    pub(crate) teams: Vec<String>,
+
+    /// The rule of the CODEOWNERS file that assigns each of the teams, in
+    /// the same order as `teams`.
+    pub(crate) rules: Vec<String>,
+}
+
+/// A commit that changed the owners of the path.
+#[derive(Serialize)]
+pub(crate) struct OwnershipChange {
+    /// The name of the commit.
+    pub(crate) commit: String,
+
+    /// The date of the commit.
+    pub(crate) date: String,
+
+    /// The author of the commit.
+    pub(crate) author: String,
+
+    /// The first line of the message of the commit.
+    pub(crate) summary: String,
+
+    /// The owners of the path before the commit.
+    pub(crate) before: Ownership,
+
+    /// The owners of the path after the commit.
+    pub(crate) after: Ownership,
+}
+
+/// The result of looking at the history of a path.
+#[derive(Serialize, Default)]
+#[serde(transparent)]
+pub(crate) struct HistoryResult {
+    pub(crate) changes: Vec<OwnershipChange>,
+}
+
+pub fn history(path: &Path, codeowners: &Path) -> Result<HistoryResult> {
+    let path = fs::normalize_path(path);
+    let codeowners = fs::normalize_path(codeowners);
+    let dir = codeowners.parent().unwrap_or(Path::new("."));
+    let root = git::repository_root(dir)?;
+
+    let mut changes = vec![];
+    let mut before = Ownership::default();
+
+    // Only the commits that change which teams own the path, or the rules
+    // that assign them, are interesting. Commits that delete the CODEOWNERS
+    // file leave the path without any owners.
+    for version in git::file_history(&root, &codeowners)? {
+        let after =
+            match version.contents.as_deref().map(|contents| CodeOwners::parse(contents, &root)) {
+                Some(Ok(owners)) => {
+                    let (teams, rules) = owners.lookup_rules(&path).into_iter().unzip();
+                    Ownership { teams, rules }
+                }
+                // A broken revision of the CODEOWNERS file shouldn't hide the rest
+                // of the history, so it's skipped as if it never happened.
+                Some(Err(err)) => {
+                    warn!("Skipping the CODEOWNERS file at {}: {err}", &version.commit[..7]);
+                    continue;
+                }
+                None => Ownership::default(),
+            };
+
+        if after == before {
+            continue;
+        }
+
+        changes.push(OwnershipChange {
+            commit: version.commit,
+            date: version.date,
+            author: version.author,
+            summary: version.summary,
+            before: std::mem::replace(&mut before, after.clone()),
+            after,
+        });
+    }
+
+    Ok(HistoryResult { changes })
+}
+
+#[cfg(test)]
+mod tests {
+    use teamsearch_workspace::testing::{commit_as, repository};
+
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let repo = repository(&[("CODEOWNERS", "/src/ @org/core\n"), ("src/a.rs", "")]);
+        let root = repo.path();
+
+        let alice = ("Alice", "alice@example.com");
+        commit_as(
+            root,
+            alice,
+            &[("CODEOWNERS", "/src/ @org/core\n/docs/ @org/docs\n")],
+            "Add the docs",
+        );
+        commit_as(root, alice, &[("CODEOWNERS", "/src/{ @org/web\n")], "Break the owners");
+        commit_as(root, alice, &[("CODEOWNERS", "/src/ @org/web\n")], "Hand over the sources");
+
+        let result = history(&root.join("src/a.rs"), &root.join("CODEOWNERS")).unwrap();
+        let changes = result.changes.iter().map(|change| {
+            (change.author.as_str(), change.before.teams.clone(), change.after.teams.clone())
+        });
+
+        // The commit that doesn't change the owners of the path, and the one
+        // with an invalid pattern, are skipped.
+        assert_eq!(
+            changes.collect::<Vec<_>>(),
+            [
+                ("Carol", vec![], vec!["@org/core".to_string()]),
+                ("Alice", vec!["@org/core".to_string()], vec!["@org/web".to_string()]),
+            ]
+        );
+        assert_eq!(result.changes[1].after.rules, ["/src/"]);
+    }
+}
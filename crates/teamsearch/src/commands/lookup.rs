@@ -1,10 +1,14 @@
-use std::{iter::once, path::PathBuf};
+use std::{collections::BTreeMap, iter::once, path::PathBuf};
 
 use anyhow::Result;
 use itertools::Itertools;
 use serde::Serialize;
 use teamsearch_utils::fs;
-use teamsearch_workspace::{codeowners::CodeOwners, settings::Settings};
+use teamsearch_workspace::{
+    codeowners::{CodeOwners, CodeOwnersTree},
+    registry::{Registry, TeamInfo},
+    settings::Settings,
+};
 
 /// An lookup entry, representing a file and its corresponding
 /// owners.
@@ -13,8 +17,20 @@ pub(crate) struct LookupEntry {
     /// The owner of the file, if any.
     pub(crate) teams: Vec<String>,
 
+    /// The rule of the CODEOWNERS file that assigns each of the teams, in
+    /// the same order as `teams`.
+    pub(crate) rules: Vec<String>,
+
     /// The path of the entry.
     pub(crate) path: PathBuf,
+
+    /// The CODEOWNERS file that the owners come from, which is the file of
+    /// the closest repository that contains the path.
+    pub(crate) codeowners: PathBuf,
+
+    /// The metadata of each of the teams that's in the team registry.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) metadata: BTreeMap<String, TeamInfo>,
 }
 
 /// The result of an owner lookup.
@@ -24,7 +40,11 @@ pub(crate) struct LookupResult {
     pub(crate) entries: Vec<LookupEntry>,
 }
 
-pub fn lookup(files: &[PathBuf], settings: Settings) -> Result<LookupResult> {
+pub fn lookup(
+    files: &[PathBuf],
+    settings: Settings,
+    registry: Option<&Registry>,
+) -> Result<LookupResult> {
     if files.is_empty() {
         return Ok(LookupResult::default());
     }
@@ -37,13 +57,35 @@ pub fn lookup(files: &[PathBuf], settings: Settings) -> Result<LookupResult> {
 
     // We've gotta parse in the `CODEOWNERS` file, and then
     // extract the given patterns that are specified for the particular team.
-    let codeowners = CodeOwners::parse_from_file(&settings.codeowners, &root)?;
+    let codeowners = CodeOwnersTree::new(
+        fs::normalize_path(&settings.codeowners),
+        CodeOwners::parse_from_settings(&settings, &root)?,
+    );
 
     let mut entries = Vec::new();
 
     // For each path (other than last), we need to find the team that owns it.
     for path in paths.iter().take(paths.len() - 1) {
-        entries.push(LookupEntry { path: path.clone(), teams: codeowners.lookup(path) });
+        // The files of a git revision don't exist, so there aren't any nested
+        // repositories to look for.
+        if settings.revision.is_none() {
+            codeowners.discover_ancestors(path)?;
+        }
+
+        let file = codeowners.resolve(path);
+        let (teams, rules): (Vec<_>, _) = file.codeowners.lookup_rules(path).into_iter().unzip();
+        let metadata = teams
+            .iter()
+            .filter_map(|team| Some((team.clone(), registry?.get(team)?.clone())))
+            .collect();
+
+        entries.push(LookupEntry {
+            path: path.clone(),
+            teams,
+            rules,
+            codeowners: file.path.clone(),
+            metadata,
+        });
     }
 
     Ok(LookupResult { entries })
@@ -1,6 +1,10 @@
 //! Defines all of the commands that are available to the user.
 
+pub mod check_staged;
 pub mod find;
+pub mod history;
 pub mod lookup;
 pub mod orphans;
+pub mod suggest;
+pub mod validate;
 pub mod version;
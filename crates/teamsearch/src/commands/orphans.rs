@@ -6,7 +6,7 @@ use rayon::prelude::*;
 use serde::Serialize;
 use teamsearch_utils::{fs, thread_pool};
 use teamsearch_workspace::{
-    codeowners::CodeOwners,
+    codeowners::{CodeOwners, CodeOwnersTree},
     resolver::{ResolvedFile, find_files_in_paths},
     settings::{FilePattern, Settings},
 };
@@ -35,7 +35,10 @@ pub fn orphans(
 
     // We've gotta parse in the `CODEOWNERS` file, and then
     // extract the given patterns that are specified for the particular team.
-    let codeowners = CodeOwners::parse_from_file(&settings.codeowners, &root)?;
+    let codeowners = CodeOwnersTree::new(
+        fs::normalize_path(&settings.codeowners),
+        CodeOwners::parse_from_settings(&settings, &root)?,
+    );
 
     // Add exclusions from the user:
     settings.file_resolver.user_exclude =
@@ -44,28 +47,26 @@ pub fn orphans(
     settings.file_resolver.include =
         settings.file_resolver.include.extend(vec![FilePattern::all()])?;
 
-    let all_files =
-        find_files_in_paths(&root, files, &settings)?.into_iter().collect::<Result<Vec<_>, _>>()?;
+    let all_files = find_files_in_paths(&root, files, &settings, Some(&codeowners))?
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Each of the files is owned according to the CODEOWNERS file of the
+    // closest repository that contains it.
+    let is_owned =
+        |file: &ResolvedFile| codeowners.resolve(file.path()).codeowners.is_owned(file.path());
 
     // Depending on whether we have a small number of files, we can either
     // use a thread pool or not. Typically, for small numbers of files, we
     // don't need to use a thread pool.
     let orphans = match all_files.len() {
-        0..=1000 => {
-            all_files.into_iter().filter(|file| !codeowners.is_owned(file.path())).collect_vec()
-        }
+        0..=1000 => all_files.into_iter().filter(|file| !is_owned(file)).collect_vec(),
         _ => {
             // Construct a thread pool with limited threads.
             //
             // For a small number of files, there no need to use a thread pool.
-            let pool = thread_pool::construct_thread_pool();
-            pool.install(|| {
-                all_files
-                    .par_iter()
-                    .filter(|file| !codeowners.is_owned(file.path()))
-                    .cloned()
-                    .collect()
-            })
+            let pool = thread_pool::construct_thread_pool(settings.threads);
+            pool.install(|| all_files.par_iter().filter(|file| !is_owned(file)).cloned().collect())
         }
     };
 
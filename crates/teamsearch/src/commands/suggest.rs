@@ -0,0 +1,243 @@
+//! Implementation of the `suggest` command, which suggests owners for the
+//! orphaned files based on who has been committing to them.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use itertools::Itertools;
+use serde::Serialize;
+use teamsearch_utils::fs;
+use teamsearch_workspace::{
+    codeowners::{CodeOwners, escape_path},
+    git,
+    roster::Roster,
+    settings::Settings,
+};
+
+use crate::commands::orphans;
+
+/// A team that could own an orphaned directory.
+#[derive(Serialize)]
+pub(crate) struct Candidate {
+    /// The name of the team.
+    pub(crate) team: String,
+
+    /// The number of commits to the orphaned files by members of the team.
+    pub(crate) commits: usize,
+
+    /// The share of all of the commits to the orphaned files, between 0
+    /// and 1.
+    pub(crate) share: f64,
+}
+
+/// The suggested owners of the orphaned files within a directory.
+#[derive(Serialize)]
+pub(crate) struct Suggestion {
+    /// The directory that contains the orphaned files.
+    pub(crate) directory: PathBuf,
+
+    /// The orphaned files that are directly within the directory.
+    pub(crate) files: Vec<PathBuf>,
+
+    /// The number of commits that touched any of the orphaned files.
+    pub(crate) commits: usize,
+
+    /// The teams that could own the files, ranked by their share of the
+    /// commits.
+    pub(crate) candidates: Vec<Candidate>,
+
+    /// The CODEOWNERS rules that assign the files to the top candidate, if
+    /// there is one.
+    pub(crate) rules: Vec<String>,
+}
+
+/// The result of suggesting owners.
+#[derive(Serialize, Default)]
+#[serde(transparent)]
+pub(crate) struct SuggestResult {
+    pub(crate) suggestions: Vec<Suggestion>,
+}
+
+pub fn suggest(
+    files: &[PathBuf],
+    settings: Settings,
+    exclusions: Vec<String>,
+    roster: &Roster,
+    since: Option<&str>,
+) -> Result<SuggestResult> {
+    let codeowners_path = fs::normalize_path(&settings.codeowners);
+    let orphans = orphans::orphans(files, settings, exclusions)?.orphans;
+    if orphans.is_empty() {
+        return Ok(SuggestResult::default());
+    }
+
+    // Group the orphaned files by the directory that they're in.
+    let mut directories: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    for orphan in orphans {
+        let path = orphan.into_path();
+        let directory = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        directories.entry(directory).or_default().push(path);
+    }
+
+    // The rules of the CODEOWNERS file are relative to the root of the
+    // repository.
+    let dir = codeowners_path.parent().unwrap_or(Path::new("."));
+    let root = git::repository_root(dir)?;
+    let codeowners = CodeOwners::parse_from_file(&codeowners_path, &root)?;
+
+    // Count the commits of each of the teams to each of the directories, a
+    // commit only counts once for each directory however many of the files
+    // it touched.
+    let paths = files.iter().map(fs::normalize_path).collect_vec();
+    let orphans: HashSet<&Path> = directories.values().flatten().map(PathBuf::as_path).collect();
+    let mut commits: HashMap<&Path, (usize, HashMap<&str, usize>)> = HashMap::new();
+
+    for commit in git::commits(&root, &paths, since)? {
+        let touched = commit
+            .files
+            .iter()
+            .filter(|file| orphans.contains(file.as_path()))
+            .filter_map(|file| file.parent())
+            .unique();
+
+        let teams = roster.teams_of(&commit.email, &commit.name);
+        for directory in touched {
+            let Some((directory, _)) = directories.get_key_value(directory) else { continue };
+            let (total, counts) = commits.entry(directory).or_default();
+            *total += 1;
+            for team in teams {
+                *counts.entry(team).or_default() += 1;
+            }
+        }
+    }
+
+    let suggestions = directories
+        .iter()
+        .map(|(directory, files)| {
+            let (total, counts) = commits.remove(directory.as_path()).unwrap_or_default();
+            let candidates = counts
+                .into_iter()
+                .map(|(team, commits)| Candidate {
+                    team: team.to_string(),
+                    commits,
+                    share: commits as f64 / total as f64,
+                })
+                .sorted_by(|a, b| b.commits.cmp(&a.commits).then_with(|| a.team.cmp(&b.team)))
+                .collect_vec();
+
+            let rules = match candidates.first() {
+                Some(candidate) => rules(&root, directory, files, &codeowners)?
+                    .into_iter()
+                    .map(|rule| format!("{rule} {}", candidate.team))
+                    .collect(),
+                None => vec![],
+            };
+
+            Ok(Suggestion {
+                directory: directory.clone(),
+                files: files.clone(),
+                commits: total,
+                candidates,
+                rules,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SuggestResult { suggestions })
+}
+
+/// Get the CODEOWNERS patterns that cover the orphaned `files` of a
+/// `directory`. If none of the other files of the directory are owned, then
+/// a single pattern covers all of the files of the directory, otherwise each
+/// of the orphaned files gets its own pattern so that the existing owners of
+/// the directory aren't overridden.
+fn rules(
+    root: &Path,
+    directory: &Path,
+    files: &[PathBuf],
+    codeowners: &CodeOwners,
+) -> Result<Vec<String>> {
+    let pattern = |path: &Path| {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        format!("/{}", escape_path(&relative.to_string_lossy()))
+    };
+
+    let has_owned_files = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|ft| ft.is_file()))
+        .any(|entry| codeowners.is_owned(&entry.path()));
+
+    if has_owned_files {
+        Ok(files.iter().map(|file| pattern(file)).collect())
+    } else if directory == root {
+        Ok(vec!["/*".to_string()])
+    } else {
+        Ok(vec![format!("{}/*", pattern(directory))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use teamsearch_workspace::testing::{commit_as, repository};
+
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        let repo = repository(&[
+            ("CODEOWNERS", "/owned/ @org/core\n/mixed/owned.rs @org/core\n"),
+            ("owned/x.rs", ""),
+            ("orphan/a.rs", ""),
+            ("orphan/b.rs", ""),
+            ("mixed/owned.rs", ""),
+            ("mixed/new file.rs", ""),
+        ]);
+        let root = repo.path();
+
+        let alice = ("Alice", "alice@example.com");
+        let bob = ("Bob", "bob@example.com");
+        commit_as(root, alice, &[("orphan/a.rs", "1")], "Change a");
+        commit_as(root, alice, &[("orphan/a.rs", "2"), ("orphan/b.rs", "2")], "Change a and b");
+        commit_as(
+            root,
+            bob,
+            &[("orphan/b.rs", "3"), ("mixed/new file.rs", "3")],
+            "Change b and add a file",
+        );
+
+        let roster =
+            Roster::parse(r#"{ "@org/core": ["alice@example.com"], "@org/web": ["Bob"] }"#)
+                .unwrap();
+        let settings = Settings::new(true, root.join("CODEOWNERS"));
+        let result = suggest(&[root.to_path_buf()], settings, vec![], &roster, None).unwrap();
+
+        let suggestions = result
+            .suggestions
+            .iter()
+            .map(|suggestion| (suggestion.directory.strip_prefix(root).unwrap(), suggestion))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(suggestions.len(), 3);
+
+        // The CODEOWNERS file was only committed by someone outside of the
+        // roster, so there's nobody to suggest.
+        let top = suggestions[Path::new("")];
+        assert_eq!(top.files, [root.join("CODEOWNERS")]);
+        assert_eq!(top.commits, 1);
+        assert!(top.candidates.is_empty() && top.rules.is_empty());
+
+        // A commit only counts once, however many of the files it touched.
+        let orphan = suggestions[Path::new("orphan")];
+        assert_eq!(orphan.commits, 4);
+        let candidates = orphan.candidates.iter().map(|c| (c.team.as_str(), c.commits, c.share));
+        assert_eq!(candidates.collect_vec(), [("@org/core", 2, 0.5), ("@org/web", 1, 0.25)]);
+        assert_eq!(orphan.rules, ["/orphan/* @org/core"]);
+
+        // The directory has an owned file, so only the orphan gets a rule.
+        let mixed = suggestions[Path::new("mixed")];
+        assert_eq!(mixed.files, [root.join("mixed/new file.rs")]);
+        assert_eq!(mixed.rules, [r"/mixed/new\ file.rs @org/web"]);
+    }
+}
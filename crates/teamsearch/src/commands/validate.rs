@@ -0,0 +1,73 @@
+//! Implementation of the `validate` command, which checks that all of the
+//! owners of the CODEOWNERS file are known to the team registry.
+
+use std::path::Path;
+
+use anyhow::Result;
+use teamsearch_utils::fs;
+use teamsearch_workspace::{codeowners::CodeOwners, registry::Registry};
+
+/// An owner of the CODEOWNERS file that isn't in the registry.
+pub(crate) struct MissingOwner {
+    /// The name of the owner.
+    pub(crate) owner: String,
+
+    /// The rules of the CODEOWNERS file that assign the owner.
+    pub(crate) rules: Vec<String>,
+}
+
+/// The result of validating the CODEOWNERS file.
+#[derive(Default)]
+pub(crate) struct ValidateResult {
+    /// The number of owners that were checked.
+    pub(crate) owners: usize,
+
+    /// The owners that are missing from the registry, in the order in which
+    /// they first appear in the CODEOWNERS file.
+    pub(crate) missing: Vec<MissingOwner>,
+}
+
+pub fn validate(codeowners: &Path, registry: &Registry) -> Result<ValidateResult> {
+    let codeowners = fs::normalize_path(codeowners);
+    let root = codeowners.parent().unwrap_or(Path::new("."));
+    let codeowners = CodeOwners::parse_from_file(&codeowners, root)?;
+
+    let mut result = ValidateResult::default();
+    for owner in codeowners.teams() {
+        result.owners += 1;
+        if registry.get(owner).is_none() {
+            let rules = codeowners.get_rules_for_team(owner).to_vec();
+            result.missing.push(MissingOwner { owner: owner.to_string(), rules });
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use teamsearch_workspace::registry::RegistryFormat;
+
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let codeowners = dir.path().join("CODEOWNERS");
+        let contents = "/src/ @org/core @org/web\n/docs/ @org/docs\n/web/ @Org/Web @org/docs\n";
+        std::fs::write(&codeowners, contents).unwrap();
+
+        let registry =
+            Registry::parse(r#"{ "@org/web": {}, "@org/core": {} }"#, RegistryFormat::Json);
+        let result = validate(&codeowners, &registry.unwrap()).unwrap();
+
+        // Owners are compared without regard to case, but are still reported
+        // as they're written.
+        assert_eq!(result.owners, 4);
+        let missing = result.missing.iter().map(|m| (m.owner.as_str(), m.rules.clone()));
+        assert_eq!(
+            missing.collect::<Vec<_>>(),
+            [("@org/docs", vec!["/docs/".to_string(), "/web/".to_string()])]
+        );
+    }
+}
@@ -1,30 +1,62 @@
 //! Library definition of `teamsearch` crate.
 
 pub mod cli;
+mod colours;
 mod commands;
 mod crash;
+mod printer;
 pub(crate) mod version;
 
 use std::{
     collections::BTreeMap,
+    io::{self, IsTerminal, Write},
     panic,
     path::{Path, PathBuf},
     process::ExitCode,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
 };
 
 use anyhow::{Ok, Result, anyhow};
-use cli::{FindCommand, LookupCommand, OrphanCommand};
-use commands::{find::FindResult, lookup::LookupEntry};
+use clap::ValueEnum;
+use cli::{
+    CheckStagedCommand, ConfigCommand, ConfigSubcommand, FindCommand, GitFilesArgs, HistoryCommand,
+    JsonLinesMode, LookupCommand, OrphanCommand, OutputFormat, SortKind, SuggestCommand,
+    ValidateCommand,
+};
+use colours::Palette;
+use commands::{
+    check_staged::Unowned,
+    find::{FindOptions, FindResult, Sort},
+    history::{Ownership, OwnershipChange},
+    lookup::LookupEntry,
+    suggest::Suggestion,
+    validate::MissingOwner,
+};
 use crash::crash_handler;
-use log::info;
-use teamsearch_matcher::Match;
+use itertools::Itertools;
+use log::{error, info};
+use printer::{ripgrep::RipgrepPrinter, table::TablePrinter, template::Template};
+use serde::Serialize;
+use teamsearch_matcher::{FileMatches, Match, MatchSnippet, Pattern, SearchOptions, replace};
 use teamsearch_utils::{
-    highlight::{Colour, highlight},
+    fs,
+    highlight::{self, Colour, ColourChoice, Modifier, Style, highlight},
     lines::get_line_range,
     logging::ToolLogger,
     stream::CompilerOutputStream,
 };
-use teamsearch_workspace::settings::Settings;
+use teamsearch_workspace::{
+    config::{Config, Source, Sourced},
+    git::{self, Changes, Revision},
+    registry::Registry,
+    roster::Roster,
+    settings::{FileSubset, Settings},
+    types::FILE_TYPES,
+};
 
 #[derive(Copy, Clone)]
 pub enum ExitStatus {
@@ -52,17 +84,49 @@ pub static LOGGER: ToolLogger = ToolLogger::new();
 
 /// Handler function which will delegate functionality to the appropriate
 /// command.
-pub fn run(cli::Cli { command }: cli::Cli) -> Result<ExitStatus> {
+pub fn run(
+    cli::Cli { mut command, color, message_format, threads, registry }: cli::Cli,
+) -> Result<ExitStatus> {
     // Initial grunt work, panic handler and logger setup...
     panic::set_hook(Box::new(crash_handler));
 
-    let output_stream = CompilerOutputStream::stdout;
+    // The configuration decides where the output goes, so it's loaded before
+    // the logger, but any errors can only be reported once the logger is set.
+    // The version is printed without it, so that it works even when the
+    // configuration is broken.
+    let config = match command {
+        cli::Command::Version => Ok(Config::default()),
+        _ => std::env::current_dir().map_err(Into::into).and_then(|dir| Config::load(&dir)),
+    };
+    let config =
+        config.and_then(|config| configure(&mut command, config, color, threads, registry));
+    let colour = match &config {
+        Result::Ok(config) => config.color.as_ref().map(|color| parse_colour(&color.value)),
+        Err(_) => None,
+    };
+    let colour = colour.and_then(Result::ok).unwrap_or_default();
+
+    // When the command prints machine-readable output, all of the messages are
+    // written to `stderr` so that they don't corrupt the output.
+    let output_stream = if command.has_machine_output() {
+        CompilerOutputStream::stderr
+    } else {
+        CompilerOutputStream::stdout
+    };
     let error_stream = CompilerOutputStream::stderr;
 
+    // Whether to highlight is decided separately for each stream, since one of
+    // them might be redirected while the other is still a terminal. The
+    // results are printed to `stdout`, while the prefixes of the messages are
+    // highlighted according to the stream that they're written to.
+    highlight::set_enabled(colour.should_colour(io::stdout().is_terminal()));
+
     log::set_logger(&LOGGER).unwrap_or_else(|_| panic!("couldn't initiate logger"));
 
     LOGGER.error_stream.set(error_stream()).unwrap();
     LOGGER.output_stream.set(output_stream()).unwrap();
+    LOGGER.set_messaging_format(message_format);
+    LOGGER.set_colour_choice(colour);
 
     // If we're building in debug mode, we want to see all the logs.
     #[cfg(debug_assertions)]
@@ -71,16 +135,193 @@ pub fn run(cli::Cli { command }: cli::Cli) -> Result<ExitStatus> {
     #[cfg(not(debug_assertions))]
     log::set_max_level(log::LevelFilter::Info);
 
-    // We also need to create a global-config
+    let config = config?;
+    let threads = config.threads.as_ref().map(|threads| threads.value);
+
+    // The registry is only read by the commands that show the metadata of the
+    // owners, so that a broken registry doesn't get in the way of the others.
+    let registry = || match &config.registry {
+        Some(registry) => Registry::parse_from_file(&registry.value).map(Some),
+        None => Ok(None),
+    };
 
     match command {
-        cli::Command::Find(args) => find(args),
-        cli::Command::Lookup(args) => lookup(args),
-        cli::Command::Orphans(args) => orphans(args),
+        cli::Command::Find(args) => {
+            let registry = if args.show_owners { registry()? } else { None };
+            find(*args, threads, registry.as_ref())
+        }
+        cli::Command::Lookup(args) => lookup(args, registry()?.as_ref()),
+        cli::Command::Orphans(args) => orphans(args, threads),
+        cli::Command::Suggest(args) => suggest(args, threads),
+        cli::Command::CheckStaged(args) => check_staged(args),
+        cli::Command::History(args) => history(args),
+        cli::Command::Validate(args) => validate(args, registry()?.as_ref()),
+        cli::Command::Config(ConfigCommand { command: ConfigSubcommand::Show }) => {
+            config_show(&config)
+        }
         cli::Command::Version => version(),
     }
 }
 
+/// Fill in the options of the `command` that weren't given on the command line
+/// from the configuration, and record the global options of the command line
+/// in the configuration, since they take precedence over it.
+fn configure(
+    command: &mut cli::Command,
+    mut config: Config,
+    color: Option<ColourChoice>,
+    threads: Option<usize>,
+    registry: Option<PathBuf>,
+) -> Result<Config> {
+    if let Some(color) = color {
+        let value = color.to_possible_value().unwrap().get_name().to_string();
+        config.color = Some(Sourced::new(value, Source::Cli));
+    }
+
+    if let Some(threads) = threads {
+        config.threads = Some(Sourced::new(threads, Source::Cli));
+    }
+
+    if let Some(registry) = registry {
+        config.registry = Some(Sourced::new(registry, Source::Cli));
+    }
+
+    // Ensure that the values of the configuration are valid, even if they
+    // aren't used by the command.
+    if let Some(color) = &config.color {
+        parse_colour(&color.value)?;
+    }
+
+    let format = match &config.format {
+        Some(format) => Some(OutputFormat::from_str(&format.value, true).map_err(|_| {
+            anyhow!("Unknown output format `{}` in the {}.", format.value, format.source)
+        })?),
+        None => None,
+    };
+
+    let codeowners = config.codeowners.as_ref().map(|codeowners| codeowners.value.clone());
+    let exclude = config.exclude.iter().map(|exclude| exclude.value.clone());
+
+    // The format of the configuration only applies when the command supports
+    // it, and the output isn't already chosen by other options.
+    match command {
+        cli::Command::Find(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+            args.exclude.splice(0..0, exclude);
+            args.teams = args
+                .teams
+                .iter()
+                .map(|team| config.resolve_team(team))
+                .flatten_ok()
+                .try_collect()?;
+
+            let list_files = args.files_with_matches || args.files_without_match;
+            let format = format.filter(|format| match format {
+                OutputFormat::Csv | OutputFormat::Tsv => args.count,
+                OutputFormat::Sarif => !(list_files || args.count || args.write),
+                _ => true,
+            });
+
+            if !args.json
+                && args.jsonl.is_none()
+                && !args.rg_json
+                && !args.vimgrep
+                && args.format_template.is_none()
+            {
+                args.format = args.format.or(format);
+            }
+        }
+        cli::Command::Lookup(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+            if !args.json {
+                args.format = args.format.or(format.filter(|f| *f != OutputFormat::Sarif));
+            }
+        }
+        cli::Command::Orphans(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+            args.exclude.splice(0..0, exclude);
+            if !args.json {
+                args.format = args.format.or(format);
+            }
+        }
+        cli::Command::Suggest(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+            args.exclude.splice(0..0, exclude);
+        }
+        cli::Command::CheckStaged(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+        }
+        cli::Command::History(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+        }
+        cli::Command::Validate(args) => {
+            args.codeowners = args.codeowners.take().or(codeowners);
+        }
+        cli::Command::Config(_) | cli::Command::Version => {}
+    }
+
+    Ok(config)
+}
+
+/// Parse the colour choice of the configuration.
+fn parse_colour(color: &str) -> Result<ColourChoice> {
+    ColourChoice::from_str(color, true).map_err(|_| {
+        anyhow!("Unknown colour choice `{color}`, expected `auto`, `always` or `never`.")
+    })
+}
+
+/// Get the path of the CODEOWNERS file, which is given either on the command
+/// line or by the configuration.
+fn require_codeowners(codeowners: Option<PathBuf>) -> Result<PathBuf> {
+    codeowners.ok_or_else(|| {
+        anyhow!(
+            "The CODEOWNERS file must be specified with `--codeowners`, or in `teamsearch.toml`."
+        )
+    })
+}
+
+/// Get the files that a command is restricted to by `--changed-since`,
+/// `--staged` or `--tracked-only`, if any. The files are taken from the git
+/// repository that contains the CODEOWNERS file.
+fn git_files(args: &GitFilesArgs, codeowners: &Path) -> Result<Option<FileSubset>> {
+    let dir = &codeowners_dir(codeowners);
+
+    let changes = match (&args.changed_since, args.staged) {
+        (Some(rev), _) => Some(Changes::Since(rev)),
+        (None, true) => Some(Changes::Staged),
+        (None, false) => None,
+    };
+
+    let changed = changes
+        .map(|changes| git::changed_files(&git::repository_root(dir)?, changes))
+        .transpose()?;
+    let tracked = args.tracked_only.then(|| git::tracked_files(dir)).transpose()?;
+
+    Ok(match (changed, tracked) {
+        (Some(changed), Some(tracked)) => {
+            Some(FileSubset::new(changed).intersect(&FileSubset::new(tracked)))
+        }
+        (changed, tracked) => changed.or(tracked).map(FileSubset::new),
+    })
+}
+
+/// Resolve the git revision given by `--rev`, if any, within the repository
+/// that contains the CODEOWNERS file.
+fn revision(rev: Option<&str>, codeowners: &Path) -> Result<Option<Revision>> {
+    rev.map(|rev| Revision::new(&codeowners_dir(codeowners), rev)).transpose()
+}
+
+/// Get the directory of the CODEOWNERS file, which is where git is queried
+/// from. When reading from a git revision the directory might no longer
+/// exist, in which case the current directory is used instead.
+fn codeowners_dir(codeowners: &Path) -> PathBuf {
+    let codeowners = fs::normalize_path(codeowners);
+    match codeowners.parent() {
+        Some(dir) if dir.is_dir() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
 /// Returns the default set of files if none are provided, otherwise returns
 /// `None`.
 fn resolve_default_files(files: Vec<PathBuf>, is_stdin: bool) -> Vec<PathBuf> {
@@ -95,28 +336,32 @@ fn resolve_default_files(files: Vec<PathBuf>, is_stdin: bool) -> Vec<PathBuf> {
     }
 }
 
-/// Highlight matches in a line of text.
-fn highlight_line_matches(line_content: &str, matches: &[Match]) -> String {
+/// A match within a line, alongside the text that it should be replaced with.
+type LineMatch<'a> = (Match, Option<&'a str>);
+
+/// Highlight matches in a line of text. If a match has a replacement, then the
+/// replacement is highlighted in place of the matched text.
+fn highlight_line_matches(line_content: &str, matches: &[LineMatch<'_>], style: &Style) -> String {
     if matches.is_empty() {
         return line_content.to_string();
     }
 
     // Sort matches by start position
     let mut sorted_matches: Vec<_> = matches.iter().collect();
-    sorted_matches.sort_by_key(|m| m.start);
+    sorted_matches.sort_by_key(|(m, _)| m.start);
 
     let mut result = String::new();
     let mut last_end = 0;
 
-    for m in sorted_matches {
+    for (m, replacement) in sorted_matches {
         // Add text before match
         if m.start > last_end {
             result.push_str(&line_content[last_end..m.start]);
         }
 
-        // Add highlighted match
+        // Add highlighted match, or its replacement.
         let matched_text = &line_content[m.start..m.end.min(line_content.len())];
-        result.push_str(&highlight(Colour::Red, matched_text));
+        result.push_str(&highlight(style, replacement.unwrap_or(matched_text)));
 
         last_end = m.end.min(line_content.len());
     }
@@ -129,139 +374,792 @@ fn highlight_line_matches(line_content: &str, matches: &[Match]) -> String {
     result
 }
 
-fn find(args: FindCommand) -> Result<ExitStatus> {
+fn find(
+    args: FindCommand,
+    threads: Option<usize>,
+    registry: Option<&Registry>,
+) -> Result<ExitStatus> {
+    if args.type_list {
+        for (name, globs) in FILE_TYPES {
+            println!("{}: {}", highlight(Colour::Magenta, name), globs.join(", "));
+        }
+
+        return Ok(ExitStatus::Success);
+    }
+
+    let format = args.output_format();
     let files = resolve_default_files(args.files, false);
 
-    // Ensure that the codeowners file is present.
-    if !args.codeowners.exists() {
+    // The pattern is required by the CLI unless we're listing the file types.
+    let codeowners = require_codeowners(args.codeowners)?;
+    let Some(pattern) = args.pattern else {
+        return Err(anyhow!("The pattern must be specified."));
+    };
+
+    // Ensure that the codeowners file is present, unless it's read from a
+    // git revision.
+    let revision = revision(args.rev.as_deref(), &codeowners)?;
+    if revision.is_none() && !codeowners.exists() {
         return Err(anyhow!("The CODEOWNERS file does not exist."));
     }
 
-    let start = std::time::Instant::now();
+    let start = Instant::now();
+    let palette = Palette::new(&args.colours);
+
+    // When we only care about which files contain a match, we can stop searching
+    // a file as soon as we've found the first one.
+    let list_files = args.files_with_matches || args.files_without_match;
+    if format == OutputFormat::Sarif && (list_files || args.count || args.write) {
+        return Err(anyhow!(
+            "SARIF output can't be combined with `--files-with-matches`, `--files-without-match`, `--count` or `--write`."
+        ));
+    }
+
+    // The files of a revision aren't checked out, so they don't have a time at
+    // which they were last modified.
+    let sort = match args.sortr {
+        Some(kind) => Sort { kind, reverse: true },
+        None => Sort { kind: args.sort, reverse: false },
+    };
+    if revision.is_some() && sort.kind == SortKind::Modified {
+        return Err(anyhow!("The results can't be sorted by `modified` together with `--rev`."));
+    }
+
+    let tabular = matches!(format, OutputFormat::Csv | OutputFormat::Tsv);
+    if tabular && !args.count {
+        return Err(anyhow!("CSV and TSV output can only be used together with `--count`."));
+    }
+
+    let options = SearchOptions {
+        invert_match: args.invert_match,
+        max_count: if list_files {
+            Some(1)
+        } else {
+            args.max_count.into_iter().chain(args.max_total).min()
+        },
+        replacement: args.replace.as_deref(),
+        // The ripgrep protocol can report lines that aren't valid UTF-8, which
+        // none of the other outputs can.
+        invalid_utf8: args.rg_json,
+    };
+
+    let requested_teams = args.teams.iter().unique().count();
+    let mut settings = Settings::new(args.respect_gitignore, codeowners);
+    settings.file_resolver.select_files(&args.types, &args.types_not, &args.globs)?;
+    settings.file_resolver.subset = git_files(&args.git, &settings.codeowners)?;
+    settings.revision = revision;
+    settings.threads = threads;
+
+    let options = FindOptions {
+        teams: args.teams,
+        exclusions: args.exclude,
+        pattern: Pattern::new(&pattern, args.case_insensitive),
+        search: options,
+        // The ripgrep protocol reports statistics about every file that was
+        // searched, so we need to see the files without matches too.
+        keep_empty: args.files_without_match || args.rg_json,
+        max_total: args.max_total,
+        sort,
+    };
+
+    if args.rg_json {
+        let printer = RipgrepPrinter::new(start);
+        commands::find::find_each(&files, settings, options, |file| printer.print_file(&file))?;
+
+        printer.finish()?;
+        return Ok(ExitStatus::Success);
+    }
+
+    if let Some(mode) = args.jsonl {
+        return find_json_lines(std::io::stdout(), &files, settings, options, mode, start);
+    }
+
+    let result = commands::find::find(&files, settings, options)?;
+
+    if args.write {
+        return write_replacements(&result, requested_teams, args.dry_run);
+    }
+
+    if format == OutputFormat::Sarif {
+        printer::sarif::print_find(&result, &pattern)?;
+        return Ok(ExitStatus::Success);
+    }
+
+    if tabular {
+        let mut printer = TablePrinter::new(format, &["path", "owners", "count"])?;
+        for file in &result.file_matches {
+            let owners = file.owners.join(" ");
+            printer.row([file.path.display().to_string(), owners, file.len().to_string()])?;
+        }
+
+        printer.finish()?;
+        return Ok(ExitStatus::Success);
+    }
+
+    let template = args.format_template.or_else(|| args.vimgrep.then(Template::vimgrep));
+
+    if let Some(template) = template {
+        template.print(&result.file_matches)?;
+        return Ok(ExitStatus::Success);
+    }
 
-    let settings = Settings::new(args.respect_gitignore, args.codeowners);
-    let FindResult { file_matches } = commands::find::find(
-        &files,
-        settings,
-        args.teams,
-        args.exclude,
-        args.pattern,
-        args.case_insensitive,
-    )?;
+    let FindResult { mut file_matches, teams, .. } = result;
+
+    if args.files_without_match {
+        file_matches.retain(|result| result.is_empty());
+    }
 
     // Now, we need to print out the results based on the configuration of the user.
-    if args.json {
+    if list_files {
+        if format == OutputFormat::Json {
+            let paths = file_matches.iter().map(|result| &result.path).collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&paths)?);
+        } else {
+            for result in &file_matches {
+                let owners = args.show_owners.then_some(registry);
+                println!("{}", file_heading(result, owners, &palette));
+            }
+
+            info!(files = file_matches.len(); "found {} files in {:?}", file_matches.len(), start.elapsed());
+        }
+    } else if format == OutputFormat::Json {
         // Print out the results in JSON format.
         println!("{}", serde_json::to_string_pretty(&file_matches)?);
     } else {
-        for (idx, result) in file_matches.iter().enumerate() {
-            if args.count {
-                info!("{}: {}", result.path.display(), result.len());
+        let options = TextOptions {
+            count: args.count,
+            invert_match: args.invert_match,
+            show_owners: args.show_owners.then_some(registry),
+            palette: &palette,
+        };
+
+        if args.group_by_team {
+            for (idx, (team, files)) in group_by_team(&file_matches, &teams).iter().enumerate() {
+                // Separate the groups of each team by a blank line.
+                if idx > 0 {
+                    println!();
+                }
+
+                let team_matches = files.iter().map(|file| file.len()).sum::<usize>();
+                let heading = format!(
+                    "{}: {} matches in {} files",
+                    team.unwrap_or("unowned"),
+                    team_matches,
+                    files.len()
+                );
+
+                println!("{}", highlight(Colour::Yellow | Modifier::Bold, heading));
+                print_text_matches(files, options);
+            }
+        } else {
+            print_text_matches(&file_matches.iter().collect_vec(), options);
+        }
+        let total_matches = file_matches.iter().map(|m| m.len()).sum::<usize>();
+        info!(matches = total_matches; "found {} matches in {:?}", total_matches, start.elapsed());
+    }
+
+    Ok(ExitStatus::Success)
+}
+
+/// Options that control how the matches are printed as text.
+#[derive(Clone, Copy)]
+struct TextOptions<'a> {
+    /// Only print the number of matches of each file.
+    count: bool,
+
+    /// Whether the matches are inverted, i.e. span the lines that didn't
+    /// match the pattern.
+    invert_match: bool,
+
+    /// Print the teams that own each file next to its path, along with their
+    /// metadata if they're in the registry.
+    show_owners: Option<Option<&'a Registry>>,
+
+    /// The styles that are used to highlight the results.
+    palette: &'a Palette,
+}
+
+/// Print the matches of the given files as text.
+fn print_text_matches(files: &[&FileMatches], options: TextOptions<'_>) {
+    for (idx, result) in files.iter().enumerate() {
+        if options.count {
+            if let Some(registry) = options.show_owners {
+                info!(
+                    path:% = result.path.display(), owners:serde = result.owners, matches = result.len();
+                    "{} {}: {}", result.path.display(), owners_label(result, registry), result.len()
+                );
             } else {
-                // Group matches by line number, keeping track of all matches on each line.
-                let mut line_matches: BTreeMap<usize, (String, Vec<Match>)> = BTreeMap::new();
+                info!(
+                    path:% = result.path.display(), matches = result.len();
+                    "{}: {}", result.path.display(), result.len()
+                );
+            }
+        } else {
+            // Group matches by line number, keeping track of all matches on each line.
+            let mut line_matches: BTreeMap<usize, (String, Vec<LineMatch<'_>>)> = BTreeMap::new();
 
-                for m in &result.matches {
-                    let (line_num, line_start, line_end) =
-                        get_line_range(&result.contents, m.start);
+            for (index, m) in result.matches.iter().enumerate() {
+                let (line_num, line_start, line_end) = get_line_range(&result.contents, m.start);
 
-                    // Get the line content
-                    let line_content = result.contents[line_start..line_end].trim_end().to_string();
+                // Get the line content
+                let line_content = result.contents[line_start..line_end].trim_end().to_string();
 
-                    // Adjust match positions relative to line start
-                    let adjusted_match = Match {
+                // Adjust match positions relative to line start
+                let adjusted_match = (
+                    Match {
                         start: m.start.saturating_sub(line_start),
                         end: m.end.saturating_sub(line_start),
+                    },
+                    result.replacement(index),
+                );
+
+                line_matches
+                    .entry(line_num)
+                    .and_modify(|(_, matches)| {
+                        matches.push(adjusted_match);
+                    })
+                    .or_insert_with(|| (line_content, vec![adjusted_match]));
+            }
+
+            // Print file path followed by all matching lines.
+            if !line_matches.is_empty() {
+                // File path in magenta/pink
+                println!("{}", file_heading(result, options.show_owners, options.palette));
+
+                for (line_num, (line_content, matches)) in &line_matches {
+                    // Highlight matches in the line, inverted matches span the
+                    // whole line so there's nothing to highlight.
+                    let highlighted_line = if options.invert_match {
+                        line_content.clone()
+                    } else {
+                        highlight_line_matches(line_content, matches, &options.palette.matched)
                     };
 
-                    line_matches
-                        .entry(line_num)
-                        .and_modify(|(_, matches)| {
-                            matches.push(adjusted_match);
-                        })
-                        .or_insert_with(|| (line_content, vec![adjusted_match]));
+                    // Line number in bright green, then the highlighted line
+                    println!("{}:{}", highlight(&options.palette.line, line_num), highlighted_line);
                 }
 
-                // Print file path followed by all matching lines.
-                if !line_matches.is_empty() {
-                    // File path in magenta/pink
-                    println!("{}", highlight(Colour::Magenta, result.path.display()));
+                // Only print blank line between files, not after the last one.
+                if idx < files.len() - 1 {
+                    println!();
+                }
+            }
+        }
+    }
+}
 
-                    for (line_num, (line_content, matches)) in &line_matches {
-                        // Highlight matches in the line
-                        let highlighted_line = highlight_line_matches(line_content, matches);
+/// Format the teams that own a file, as they're shown next to its path. The
+/// teams that are in the `registry` are followed by their metadata.
+fn owners_label(file: &FileMatches, registry: Option<&Registry>) -> String {
+    if file.owners.is_empty() {
+        return "[unowned]".to_string();
+    }
 
-                        // Line number in bright green, then the highlighted line
-                        println!("{}:{}", highlight(Colour::Green, line_num), highlighted_line);
-                    }
+    match registry {
+        Some(registry) => {
+            format!("[{}]", file.owners.iter().map(|o| registry.describe(o)).join("; "))
+        }
+        None => format!("[{}]", file.owners.join(", ")),
+    }
+}
+
+/// Format the path of a file as the heading of its matches, optionally
+/// followed by the teams that own the file.
+fn file_heading(
+    file: &FileMatches,
+    show_owners: Option<Option<&Registry>>,
+    palette: &Palette,
+) -> String {
+    let path = highlight(&palette.path, file.path.display());
+
+    match show_owners {
+        Some(registry) => {
+            format!("{path} {}", highlight(Colour::Cyan, owners_label(file, registry)))
+        }
+        None => path,
+    }
+}
+
+/// Group the matched files by the teams that own them, ordered by the name of
+/// the team, with the files that aren't owned by any team last. If the search
+/// was scoped to some teams, then only those teams are used for grouping.
+fn group_by_team<'a>(
+    files: &'a [FileMatches],
+    teams: &[String],
+) -> Vec<(Option<&'a str>, Vec<&'a FileMatches>)> {
+    let mut groups: BTreeMap<Option<&str>, Vec<&FileMatches>> = BTreeMap::new();
+
+    for file in files {
+        let mut owners =
+            file.owners.iter().filter(|owner| teams.is_empty() || teams.contains(owner)).peekable();
+
+        if owners.peek().is_none() {
+            groups.entry(None).or_default().push(file);
+        }
+
+        for owner in owners {
+            groups.entry(Some(owner.as_str())).or_default().push(file);
+        }
+    }
+
+    let unowned = groups.remove(&None);
+    groups.into_iter().chain(unowned.map(|files| (None, files))).collect()
+}
+
+/// A single object of the `--jsonl` output of `find`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLine<'a> {
+    /// All of the matches within a file.
+    File(&'a FileMatches),
+
+    /// A single match within a file.
+    Match {
+        path: &'a Path,
+        owners: &'a [String],
+        #[serde(flatten)]
+        snippet: MatchSnippet<'a>,
+    },
+
+    /// The summary of the whole search, this is always the last object.
+    Summary { files: usize, searched: usize, matches: usize, elapsed: Duration },
+}
+
+/// Run a search and stream the results as JSON Lines to `out`, emitting the
+/// results of each file as soon as the file has been searched.
+fn find_json_lines(
+    out: impl Write + Send,
+    files: &[PathBuf],
+    settings: Settings,
+    options: FindOptions<'_>,
+    mode: JsonLinesMode,
+    start: Instant,
+) -> Result<ExitStatus> {
+    let out = Mutex::new(out);
+    let write_line = |line: &JsonLine<'_>| -> Result<()> {
+        let mut out = out.lock().unwrap();
+        serde_json::to_writer(&mut *out, line)?;
+        writeln!(out)?;
+        Ok(())
+    };
+
+    let totals = AtomicUsize::new(0);
+    let matched_files = AtomicUsize::new(0);
+
+    let result = commands::find::find_each(files, settings, options, |file| {
+        totals.fetch_add(file.len(), Ordering::Relaxed);
+        matched_files.fetch_add(1, Ordering::Relaxed);
+
+        match mode {
+            JsonLinesMode::File => write_line(&JsonLine::File(&file)),
+            JsonLinesMode::Match => file.snippets().into_iter().try_for_each(|snippet| {
+                write_line(&JsonLine::Match { path: &file.path, owners: &file.owners, snippet })
+            }),
+        }
+    })?;
+
+    write_line(&JsonLine::Summary {
+        files: matched_files.into_inner(),
+        searched: result.searched,
+        matches: totals.into_inner(),
+        elapsed: start.elapsed(),
+    })?;
 
-                    // Only print blank line between files, not after the last one.
-                    if idx < file_matches.len() - 1 {
-                        println!();
+    Ok(ExitStatus::Success)
+}
+
+/// Apply the replacements of a search to each of the matched files, or print
+/// them as a unified diff if this is a `dry_run`. Only the files that are owned
+/// by the selected teams are modified.
+fn write_replacements(
+    result: &FindResult,
+    requested_teams: usize,
+    dry_run: bool,
+) -> Result<ExitStatus> {
+    // If any of the requested teams don't exist, the search would've been widened
+    // to the whole repository, which we definitely don't want to rewrite. The
+    // same goes for a search without any teams at all.
+    if requested_teams == 0 || result.teams.len() != requested_teams {
+        return Err(anyhow!(
+            "Refusing to write replacements, some of the teams are not present in the CODEOWNERS file."
+        ));
+    }
+
+    let cwd = std::env::current_dir()?;
+    let (mut total_files, mut total_matches) = (0, 0);
+
+    for file in &result.file_matches {
+        if file.is_empty() || !result.is_in_scope(&file.path) {
+            continue;
+        }
+
+        if dry_run {
+            let path = file.path.strip_prefix(&cwd).unwrap_or(&file.path).display();
+            print!("{}", replace::unified_diff(file, &format!("a/{path}"), &format!("b/{path}")));
+        } else {
+            std::fs::write(&file.path, replace::apply(file))?;
+        }
+
+        total_files += 1;
+        total_matches += file.len();
+    }
+
+    if dry_run {
+        info!(
+            matches = total_matches, files = total_files;
+            "would replace {} matches in {} files", total_matches, total_files
+        );
+    } else {
+        info!(
+            matches = total_matches, files = total_files;
+            "replaced {} matches in {} files", total_matches, total_files
+        );
+    }
+
+    Ok(ExitStatus::Success)
+}
+
+fn lookup(args: LookupCommand, registry: Option<&Registry>) -> Result<ExitStatus> {
+    let format = args.output_format();
+    let files = resolve_default_files(args.files, false);
+    let codeowners = require_codeowners(args.codeowners)?;
+
+    // Ensure that the codeowners file is present, unless it's read from a
+    // git revision.
+    let revision = revision(args.rev.as_deref(), &codeowners)?;
+    if revision.is_none() && !codeowners.exists() {
+        return Err(anyhow!("The CODEOWNERS file does not exist."));
+    }
+
+    // When restricted to a subset of the files, we look up each of those
+    // files within the given paths, rather than the paths themselves.
+    let subset = git_files(&args.git, &codeowners)?;
+    let files = match subset {
+        Some(subset) => {
+            let paths = files.iter().map(fs::normalize_path).collect_vec();
+            subset
+                .files()
+                .filter(|file| paths.iter().any(|path| file.starts_with(path)))
+                .cloned()
+                .sorted()
+                .collect()
+        }
+        None => files,
+    };
+
+    let outer = fs::normalize_path(&codeowners);
+    let mut settings = Settings::new(true, codeowners);
+    settings.revision = revision;
+    let results = commands::lookup::lookup(&files, settings, registry)?;
+
+    match format {
+        OutputFormat::Json => {
+            // Print out the results in JSON format.
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        }
+        OutputFormat::Sarif => {
+            return Err(anyhow!("SARIF output isn't supported by `lookup`."));
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            // Files without any owners still get a row, with the owner and
+            // rule left empty. The metadata of the owners is only included
+            // when there's a registry.
+            let mut header = vec!["path", "owner", "rule", "codeowners"];
+            if registry.is_some() {
+                header.extend(["name", "channel", "on_call", "email"]);
+            }
+
+            let mut printer = TablePrinter::new(format, &header)?;
+            for LookupEntry { path, teams, rules, codeowners, metadata } in &results.entries {
+                let path = path.display().to_string();
+                let codeowners = codeowners.display().to_string();
+                let row = |team: &str, rule: &str| {
+                    let mut row = vec![path.clone(), team.into(), rule.into(), codeowners.clone()];
+                    if registry.is_some() {
+                        let info = metadata.get(team).cloned().unwrap_or_default();
+                        row.extend(
+                            [info.name, info.channel, info.on_call, info.email]
+                                .map(Option::unwrap_or_default),
+                        );
                     }
+                    row
+                };
+
+                if teams.is_empty() {
+                    printer.row(row("", ""))?;
+                }
+
+                for (team, rule) in teams.iter().zip(rules) {
+                    printer.row(row(team, rule))?;
                 }
             }
+
+            printer.finish()?;
         }
+        OutputFormat::Text => {
+            for LookupEntry { path, teams, codeowners, .. } in results.entries {
+                // The CODEOWNERS file is only mentioned when it's the file of
+                // a nested repository, rather than the given one.
+                let source = if codeowners == outer {
+                    String::new()
+                } else {
+                    format!(" (from {})", codeowners.display())
+                };
 
-        let total_matches = file_matches.iter().map(|m| m.len()).sum::<usize>();
-        info!("found {} matches in {:?}", total_matches, start.elapsed());
+                if teams.is_empty() {
+                    info!(path:% = path.display(); "{}: none{source}", path.display());
+                    continue;
+                }
+
+                for team in teams {
+                    let owner = registry.map_or_else(|| team.clone(), |r| r.describe(&team));
+                    info!(
+                        path:% = path.display(), team:% = team;
+                        "{}: {}{source}", path.display(), owner
+                    )
+                }
+            }
+        }
     }
 
     Ok(ExitStatus::Success)
 }
 
-fn lookup(args: LookupCommand) -> Result<ExitStatus> {
+fn orphans(args: OrphanCommand, threads: Option<usize>) -> Result<ExitStatus> {
+    let format = args.output_format();
     let files = resolve_default_files(args.files, false);
+    let codeowners = require_codeowners(args.codeowners)?;
 
-    // Ensure that the codeowners file is present.
-    if !args.codeowners.exists() {
+    // Ensure that the codeowners file is present, unless it's read from a
+    // git revision.
+    let revision = revision(args.rev.as_deref(), &codeowners)?;
+    if revision.is_none() && !codeowners.exists() {
         return Err(anyhow!("The CODEOWNERS file does not exist."));
     }
 
-    let settings = Settings::new(true, args.codeowners);
-    let results = commands::lookup::lookup(&files, settings)?;
+    let start = Instant::now();
+    let mut settings = Settings::new(true, codeowners);
+    settings.file_resolver.subset = git_files(&args.git, &settings.codeowners)?;
+    settings.revision = revision.clone();
+    settings.threads = threads;
+    let results = commands::orphans::orphans(&files, settings, args.exclude)?;
 
-    if args.json {
-        // Print out the results in JSON format.
-        println!("{}", serde_json::to_string_pretty(&results)?);
-    } else {
-        for LookupEntry { path, teams } in results.entries {
-            //
-            if teams.is_empty() {
-                info!("{}: none", path.display());
-                continue;
+    match format {
+        OutputFormat::Json => {
+            // Print out the results in JSON format.
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        }
+        OutputFormat::Sarif => printer::sarif::print_orphans(&results)?,
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let mut printer = TablePrinter::new(format, &["path", "size", "extension"])?;
+            for result in &results.orphans {
+                let path = result.path();
+                let size = match &revision {
+                    Some(revision) => revision.size(path)?,
+                    None => std::fs::metadata(path)?.len(),
+                };
+                let extension = path.extension().unwrap_or_default().to_string_lossy();
+                printer.row([path.display().to_string(), size.to_string(), extension.into()])?;
             }
 
-            for team in teams {
-                info!("{}: {}", path.display(), team)
+            printer.finish()?;
+        }
+        OutputFormat::Text => {
+            for result in &results.orphans {
+                info!(path:% = result.path().display(); "{}", result.path().display())
             }
+
+            info!(
+                files = results.orphans.len();
+                "found {} files in {:?}", results.orphans.len(), start.elapsed()
+            );
         }
     }
 
     Ok(ExitStatus::Success)
 }
 
-fn orphans(args: OrphanCommand) -> Result<ExitStatus> {
+fn suggest(args: SuggestCommand, threads: Option<usize>) -> Result<ExitStatus> {
     let files = resolve_default_files(args.files, false);
+    let codeowners = require_codeowners(args.codeowners)?;
 
     // Ensure that the codeowners file is present.
-    if !args.codeowners.exists() {
+    if !codeowners.exists() {
         return Err(anyhow!("The CODEOWNERS file does not exist."));
     }
 
-    let start = std::time::Instant::now();
-    let settings = Settings::new(true, args.codeowners);
-    let results = commands::orphans::orphans(&files, settings, args.exclude)?;
+    let roster = Roster::parse_from_file(&args.roster)?;
+    let mut settings = Settings::new(true, codeowners);
+    settings.threads = threads;
+    let results =
+        commands::suggest::suggest(&files, settings, args.exclude, &roster, Some(&args.since))?;
 
     if args.json {
-        // Print out the results in JSON format.
         println!("{}", serde_json::to_string_pretty(&results)?);
-    } else {
-        for result in &results.orphans {
-            info!("{}", result.path().display())
+        return Ok(ExitStatus::Success);
+    }
+
+    for Suggestion { directory, files, commits, candidates, .. } in &results.suggestions {
+        let candidates = match candidates.as_slice() {
+            [] => "no candidates".to_string(),
+            candidates => candidates
+                .iter()
+                .map(|c| format!("{} ({} commits, {:.0}%)", c.team, c.commits, c.share * 100.0))
+                .join(", "),
+        };
+
+        info!(
+            path:% = directory.display(), files = files.len(), commits = *commits;
+            "{}: {} orphaned files, {} commits: {}",
+            directory.display(), files.len(), commits, candidates
+        );
+    }
+
+    // The proposed rules are printed on their own, so that they can be copied
+    // into the CODEOWNERS file.
+    let rules = results.suggestions.iter().flat_map(|s| &s.rules).collect_vec();
+    if !rules.is_empty() {
+        info!("proposed CODEOWNERS rules:");
+        for rule in rules {
+            println!("{rule}");
+        }
+    }
+
+    Ok(ExitStatus::Success)
+}
+
+fn check_staged(args: CheckStagedCommand) -> Result<ExitStatus> {
+    let start = Instant::now();
+    let codeowners = require_codeowners(args.codeowners)?;
+    let result = commands::check_staged::check_staged(&codeowners)?;
+
+    if result.unowned.is_empty() {
+        info!(
+            files = result.checked;
+            "all {} new files are owned, checked in {:?}", result.checked, start.elapsed()
+        );
+        return Ok(ExitStatus::Success);
+    }
+
+    for Unowned { path, .. } in &result.unowned {
+        error!(path:% = path.display(); "{} isn't owned by any team", path.display());
+    }
+
+    // The suggested rules are printed on their own, so that they can be copied
+    // into the CODEOWNERS file.
+    let rules = result.unowned.iter().filter_map(|unowned| unowned.rule.as_ref()).collect_vec();
+    if !rules.is_empty() {
+        info!("suggested CODEOWNERS rules:");
+        for rule in rules {
+            println!("{rule}");
         }
+    }
 
-        info!("found {} files in {:?}", results.orphans.len(), start.elapsed());
+    Ok(ExitStatus::Failure)
+}
+
+fn history(args: HistoryCommand) -> Result<ExitStatus> {
+    let codeowners = require_codeowners(args.codeowners)?;
+    let results = commands::history::history(&args.path, &codeowners)?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        return Ok(ExitStatus::Success);
+    }
+
+    let describe = |ownership: &Ownership| match ownership.teams.as_slice() {
+        [] => "none".to_string(),
+        teams => teams
+            .iter()
+            .zip(&ownership.rules)
+            .map(|(team, rule)| format!("{team} ({rule})"))
+            .join(", "),
+    };
+
+    for OwnershipChange { commit, date, author, summary, before, after } in &results.changes {
+        info!(
+            commit:% = commit, date:% = date;
+            "{date} {} {summary} ({author}): {} -> {}",
+            &commit[..commit.len().min(7)], describe(before), describe(after)
+        );
+    }
+
+    if results.changes.is_empty() {
+        info!("the owners of {} have never been set", args.path.display());
+    }
+
+    Ok(ExitStatus::Success)
+}
+
+fn validate(args: ValidateCommand, registry: Option<&Registry>) -> Result<ExitStatus> {
+    let codeowners = require_codeowners(args.codeowners)?;
+    let Some(registry) = registry else {
+        return Err(anyhow!(
+            "The team registry must be specified with `--registry`, or in `teamsearch.toml`."
+        ));
+    };
+
+    let result = commands::validate::validate(&codeowners, registry)?;
+    for MissingOwner { owner, rules } in &result.missing {
+        error!(
+            owner:% = owner;
+            "{owner} isn't in the team registry, but owns {}", rules.join(", ")
+        );
+    }
+
+    if !result.missing.is_empty() {
+        return Ok(ExitStatus::Failure);
+    }
+
+    info!(owners = result.owners; "all {} owners are in the team registry", result.owners);
+    Ok(ExitStatus::Success)
+}
+
+/// Print each of the values of the merged configuration, along with where it
+/// came from.
+fn config_show(config: &Config) -> Result<ExitStatus> {
+    let default = Source::Default;
+    let show = |key: &str, value: Option<String>, source: Option<&Source>, fallback: &str| {
+        let value = value.unwrap_or_else(|| fallback.to_string());
+        let source = source.unwrap_or(&default);
+        info!(key:% = key, source:% = source; "{key} = {value} ({source})");
+    };
+
+    let codeowners = config.codeowners.as_ref();
+    show(
+        "codeowners",
+        codeowners.map(|codeowners| codeowners.value.display().to_string()),
+        codeowners.map(|codeowners| &codeowners.source),
+        "none",
+    );
+
+    if config.exclude.is_empty() {
+        show("exclude", None, None, "none");
+    }
+
+    for exclude in &config.exclude {
+        show("exclude", Some(exclude.value.clone()), Some(&exclude.source), "");
+    }
+
+    let format = config.format.as_ref();
+    show("format", format.map(|f| f.value.clone()), format.map(|f| &f.source), "text");
+
+    let color = config.color.as_ref();
+    show("color", color.map(|c| c.value.clone()), color.map(|c| &c.source), "auto");
+
+    let threads = config.threads.as_ref();
+    show("threads", threads.map(|t| t.value.to_string()), threads.map(|t| &t.source), "auto");
+
+    let registry = config.registry.as_ref();
+    show(
+        "registry",
+        registry.map(|registry| registry.value.display().to_string()),
+        registry.map(|registry| &registry.source),
+        "none",
+    );
+
+    for (alias, teams) in &config.aliases {
+        show(&format!("aliases.{alias}"), Some(teams.value.join(", ")), Some(&teams.source), "");
     }
 
     Ok(ExitStatus::Success)
@@ -271,3 +1169,251 @@ fn version() -> Result<ExitStatus> {
     commands::version::version()?;
     Ok(ExitStatus::Success)
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use teamsearch_workspace::{config::ConfigFile, registry::RegistryFormat};
+
+    use super::*;
+
+    #[test]
+    fn test_configure_precedence() {
+        let (user_path, project_path) = (Path::new("/home/u.toml"), Path::new("/repo/p.toml"));
+        let config = || {
+            let user = "codeowners = \"USER\"\nformat = \"csv\"\nthreads = 2";
+            let project = "codeowners = \"PROJECT\"\nformat = \"json\"";
+
+            let mut config = Config::default();
+            let file = ConfigFile::parse(user).unwrap();
+            config.merge(file, user_path, Source::User(user_path.into()));
+            let file = ConfigFile::parse(project).unwrap();
+            config.merge(file, project_path, Source::Project(project_path.into()));
+            config
+        };
+
+        let configured = |args: &[&str]| {
+            let cli = cli::Cli::try_parse_from(["teamsearch"].iter().chain(args)).unwrap();
+            let mut command = cli.command;
+            let config = configure(&mut command, config(), cli.color, cli.threads, None).unwrap();
+            let cli::Command::Lookup(lookup) = command else { unreachable!() };
+            (lookup.codeowners.unwrap(), lookup.format, config.threads.unwrap())
+        };
+
+        // The project takes precedence over the user.
+        let (codeowners, format, threads) = configured(&["lookup", "a.rs"]);
+        assert_eq!(codeowners, Path::new("/repo/PROJECT"));
+        assert_eq!(format, Some(OutputFormat::Json));
+        assert_eq!((threads.value, threads.source), (2, Source::User(user_path.into())));
+
+        // The command line takes precedence over both.
+        let args = ["lookup", "-c", "CLI", "--format", "tsv", "-j", "8", "a.rs"];
+        let (codeowners, format, threads) = configured(&args);
+        assert_eq!(codeowners, Path::new("CLI"));
+        assert_eq!(format, Some(OutputFormat::Tsv));
+        assert_eq!((threads.value, threads.source), (8, Source::Cli));
+    }
+
+    #[test]
+    fn test_configure_aliases() {
+        let teams = |args: &[&str]| {
+            let mut config = Config::default();
+            let file = "[aliases]\nfrontend = [\"@org/web\", \"@org/mobile\"]\nweb = \"@web\"";
+            let path = Path::new("/repo/p.toml");
+            config.merge(ConfigFile::parse(file).unwrap(), path, Source::Project(path.into()));
+
+            let cli =
+                cli::Cli::try_parse_from(["teamsearch", "find", "-p", "x"].iter().chain(args));
+            let mut command = cli.unwrap().command;
+            configure(&mut command, config, None, None, None).unwrap();
+            let cli::Command::Find(find) = command else { unreachable!() };
+            find.teams
+        };
+
+        // Aliases are expanded from the command line, and the names that
+        // aren't aliases are teams, with or without the leading `@`.
+        assert_eq!(teams(&["-t", "frontend"]), ["@org/web", "@org/mobile"]);
+        assert_eq!(teams(&["-t", "web"]), ["@web"]);
+        assert_eq!(teams(&["-t", "org/api", "-t", "@frontend"]), ["@org/api", "@frontend"]);
+    }
+
+    #[test]
+    fn test_write_replacements() {
+        let repo = teamsearch_workspace::testing::repository(&[
+            ("CODEOWNERS", "/src/ @org/core\n/web/ @org/web\n"),
+            ("src/a.rs", "foo\n"),
+            ("web/b.ts", "foo\n"),
+            ("c.rs", "foo\n"),
+        ]);
+        let root = repo.path();
+
+        let write = |teams: &[&str]| {
+            let root = root.to_str().unwrap();
+            let codeowners = format!("{root}/CODEOWNERS");
+            let mut args = vec!["teamsearch", "find", "-p", "foo", "-r", "bar", "--write"];
+            args.extend(["-c", &codeowners, root]);
+            args.extend(teams.iter().flat_map(|team| ["-t", team]));
+
+            let cli::Command::Find(find_args) = cli::Cli::try_parse_from(args)?.command else {
+                unreachable!()
+            };
+            find(*find_args, None, None)
+        };
+
+        // Nothing can be written without any teams, or with unknown ones.
+        assert!(write(&[]).is_err());
+        assert!(write(&["@org/missing"]).is_err());
+
+        // Only the files of the selected team are modified, and neither the
+        // files of the other teams nor the unowned files are.
+        write(&["@org/core"]).unwrap();
+        let read = |path| std::fs::read_to_string(root.join(path)).unwrap();
+        assert_eq!([read("src/a.rs"), read("web/b.ts"), read("c.rs")], ["bar\n", "foo\n", "foo\n"]);
+    }
+
+    /// A writer that deletes all of the files within `dir` as soon as it's
+    /// first written to, so that only the files that were searched by then
+    /// can have any results.
+    struct DeleteOnWrite {
+        dir: PathBuf,
+        output: Vec<u8>,
+    }
+
+    impl Write for DeleteOnWrite {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.output.is_empty() {
+                for entry in std::fs::read_dir(&self.dir)? {
+                    std::fs::remove_file(entry?.path())?;
+                }
+            }
+
+            self.output.extend_from_slice(buf);
+            io::Result::Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            io::Result::Ok(())
+        }
+    }
+
+    #[test]
+    fn test_find_json_lines() {
+        let files = (0..10).map(|i| (format!("src/{i}.rs"), "foo\nfoo\n")).collect_vec();
+        let mut files =
+            files.iter().map(|(path, contents)| (path.as_str(), *contents)).collect_vec();
+        files.push(("CODEOWNERS", "/src/ @org/core\n"));
+        let repo = teamsearch_workspace::testing::repository(&files);
+        let root = repo.path();
+
+        let search = |out: &mut (dyn Write + Send), mode| {
+            let mut settings = Settings::new(true, root.join("CODEOWNERS"));
+            settings.threads = Some(1);
+            let options = FindOptions {
+                teams: vec![],
+                exclusions: vec![],
+                pattern: Pattern::new("foo", false),
+                search: SearchOptions::default(),
+                keep_empty: false,
+                max_total: None,
+                sort: Sort::default(),
+            };
+
+            let files = [root.to_path_buf()];
+            find_json_lines(out, &files, settings, options, mode, Instant::now()).unwrap();
+        };
+
+        // Each line is a standalone object, and the summary is always last.
+        let mut out = vec![];
+        search(&mut out, JsonLinesMode::Match);
+        let lines = String::from_utf8(out).unwrap();
+        let objects =
+            lines.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap());
+        let types =
+            objects.map(|object| object["type"].as_str().unwrap().to_string()).collect_vec();
+        assert_eq!(types.len(), 21);
+        assert!(types[..20].iter().all(|ty| ty == "match"));
+        assert_eq!(types[20], "summary");
+
+        // The first file is written while the search is still going, so none
+        // of the files that it deletes are searched afterwards.
+        let mut out = DeleteOnWrite { dir: root.join("src"), output: vec![] };
+        search(&mut out, JsonLinesMode::File);
+        let lines = String::from_utf8(out.output).unwrap();
+        let objects =
+            lines.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap());
+        let objects = objects.collect_vec();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(
+            (&objects[0]["type"], &objects[0]["owners"]),
+            (&"file".into(), &vec!["@org/core"].into())
+        );
+        assert_eq!((&objects[1]["type"], &objects[1]["files"]), (&"summary".into(), &1.into()));
+    }
+
+    /// A file without any matches, owned by the given `owners`.
+    fn owned_file(path: &str, owners: &[&str]) -> FileMatches {
+        let owners = owners.iter().map(|owner| owner.to_string()).collect();
+        FileMatches { path: PathBuf::from(path), owners, ..FileMatches::default() }
+    }
+
+    #[test]
+    fn test_owners_label() {
+        let registry = r##"{ "@org/web": { "name": "Web Platform", "channel": "#web" } }"##;
+        let registry = Registry::parse(registry, RegistryFormat::Json).unwrap();
+
+        let unowned = owned_file("a.rs", &[]);
+        assert_eq!(owners_label(&unowned, None), "[unowned]");
+        assert_eq!(owners_label(&unowned, Some(&registry)), "[unowned]");
+
+        // Only the teams that are in the registry have any metadata.
+        let owned = owned_file("b.rs", &["@org/web", "@org/api"]);
+        assert_eq!(owners_label(&owned, None), "[@org/web, @org/api]");
+        assert_eq!(
+            owners_label(&owned, Some(&registry)),
+            "[@org/web (Web Platform, #web); @org/api]"
+        );
+    }
+
+    #[test]
+    fn test_group_by_team() {
+        let files = [
+            owned_file("a.rs", &["@org/web"]),
+            owned_file("b.rs", &[]),
+            owned_file("c.rs", &["@org/web", "@org/api"]),
+            owned_file("d.rs", &["@org/docs"]),
+        ];
+
+        let groups = |teams: &[&str]| {
+            let teams = teams.iter().map(|team| team.to_string()).collect_vec();
+            let groups = group_by_team(&files, &teams).into_iter().map(|(team, files)| {
+                (
+                    team.map(str::to_string),
+                    files.iter().map(|file| file.path.display().to_string()).collect_vec(),
+                )
+            });
+            groups.collect_vec()
+        };
+
+        // The teams are ordered by name, a file appears under each of its
+        // owners, and the files without any owners come last.
+        let group = |team: Option<&str>, paths: &[&str]| {
+            (team.map(str::to_string), paths.iter().map(|path| path.to_string()).collect_vec())
+        };
+        assert_eq!(
+            groups(&[]),
+            [
+                group(Some("@org/api"), &["c.rs"]),
+                group(Some("@org/docs"), &["d.rs"]),
+                group(Some("@org/web"), &["a.rs", "c.rs"]),
+                group(None, &["b.rs"]),
+            ]
+        );
+
+        // The owners that the search wasn't scoped to are left out, so their
+        // files count as unowned.
+        assert_eq!(
+            groups(&["@org/web"]),
+            [group(Some("@org/web"), &["a.rs", "c.rs"]), group(None, &["b.rs", "d.rs"])]
+        );
+    }
+}
@@ -18,7 +18,7 @@ pub fn main() -> ExitCode {
     match run(args) {
         Ok(status) => status.into(),
         Err(e) => {
-            eprintln!("error: {e}");
+            log::error!("{e}");
             ExitCode::from(1)
         }
     }
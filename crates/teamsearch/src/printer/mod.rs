@@ -0,0 +1,6 @@
+//! Printers for the machine-readable output formats of `teamsearch`.
+
+pub(crate) mod ripgrep;
+pub(crate) mod sarif;
+pub(crate) mod table;
+pub(crate) mod template;
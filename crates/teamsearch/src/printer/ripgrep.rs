@@ -0,0 +1,377 @@
+//! A printer that emits the results of `find` using the JSON Lines message
+//! protocol of ripgrep (i.e. `rg --json`), so that existing consumers of that
+//! protocol can switch over to `teamsearch`. The protocol consists of the
+//! following messages:
+//!
+//! - `begin`: emitted before the matches of a file.
+//!
+//! - `match`: emitted for each set of lines that contain one or more matches.
+//!
+//! - `end`: emitted after the matches of a file, with the statistics of the
+//!   search of that file.
+//!
+//! - `summary`: emitted once at the end, with the aggregated statistics.
+//!
+//! The only addition to the protocol is an `owners` field on the `begin` and
+//! `match` messages, which lists the teams that own the file. Since we don't
+//! support context lines, no `context` messages are ever emitted.
+//!
+//! The `lines` and `submatches` of a `match` message, as well as paths, are
+//! reported as base64 encoded `bytes` when they aren't valid UTF-8, and as
+//! `text` otherwise.
+
+use std::{
+    borrow::Cow,
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde::Serialize;
+use teamsearch_matcher::FileMatches;
+use teamsearch_utils::lines::LineIndex;
+
+/// Arbitrary data, which is represented as text if it is valid UTF-8, and
+/// otherwise as base64 encoded bytes.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Data<'a> {
+    Text { text: Cow<'a, str> },
+    Bytes { bytes: String },
+}
+
+impl<'a> Data<'a> {
+    fn from_bytes(bytes: &'a [u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Data::Text { text: Cow::Borrowed(text) },
+            Err(_) => Data::Bytes { bytes: BASE64_STANDARD.encode(bytes) },
+        }
+    }
+
+    fn from_path(path: &'a Path) -> Self {
+        Data::from_bytes(path.as_os_str().as_encoded_bytes())
+    }
+}
+
+/// A duration, as it is reported by the protocol.
+#[derive(Serialize, Clone)]
+struct Elapsed {
+    secs: u64,
+    nanos: u32,
+    human: String,
+}
+
+impl From<Duration> for Elapsed {
+    fn from(duration: Duration) -> Self {
+        Elapsed {
+            secs: duration.as_secs(),
+            nanos: duration.subsec_nanos(),
+            human: format!("{:.6}s", duration.as_secs_f64()),
+        }
+    }
+}
+
+impl Default for Elapsed {
+    fn default() -> Self {
+        Duration::ZERO.into()
+    }
+}
+
+/// The statistics of the search of a single file, or of the whole search. We
+/// don't time the search of individual files, so their `elapsed` is always
+/// zero.
+#[derive(Serialize, Default, Clone)]
+struct Stats {
+    elapsed: Elapsed,
+    searches: u64,
+    searches_with_match: u64,
+    bytes_searched: u64,
+    bytes_printed: u64,
+    matched_lines: u64,
+    matches: u64,
+}
+
+impl Stats {
+    fn add(&mut self, other: &Stats) {
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.bytes_printed += other.bytes_printed;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+/// A match within the `lines` of a `match` message.
+#[derive(Serialize)]
+struct SubMatch<'a> {
+    #[serde(rename = "match")]
+    matched: Data<'a>,
+    start: usize,
+    end: usize,
+}
+
+/// A single message of the protocol.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+enum Message<'a> {
+    Begin {
+        path: Data<'a>,
+        owners: &'a [String],
+    },
+    Match {
+        path: Data<'a>,
+        lines: Data<'a>,
+        line_number: usize,
+        absolute_offset: usize,
+        submatches: Vec<SubMatch<'a>>,
+        owners: &'a [String],
+    },
+    End {
+        path: Data<'a>,
+        binary_offset: Option<usize>,
+        stats: Stats,
+    },
+    Summary {
+        elapsed_total: Elapsed,
+        stats: Stats,
+    },
+}
+
+/// Printer for the ripgrep JSON protocol. Each file is written to `stdout` as
+/// a single block, so this can be used while files are searched in parallel.
+pub(crate) struct RipgrepPrinter {
+    /// When the search started.
+    start: Instant,
+
+    /// The statistics of all of the files that have been printed so far.
+    totals: Mutex<Stats>,
+}
+
+impl RipgrepPrinter {
+    pub(crate) fn new(start: Instant) -> Self {
+        RipgrepPrinter { start, totals: Mutex::new(Stats::default()) }
+    }
+
+    /// Print all of the messages for a single file that has been searched. If
+    /// the file has no matches, nothing is printed but the file still counts
+    /// towards the statistics of the search.
+    pub(crate) fn print_file(&self, file: &FileMatches) -> Result<()> {
+        self.write_file(&mut std::io::stdout(), file)
+    }
+
+    /// Print the `summary` message of the whole search.
+    pub(crate) fn finish(self) -> Result<()> {
+        self.write_summary(&mut std::io::stdout())
+    }
+
+    /// Write all of the messages for a single file to `out` at once, see
+    /// [RipgrepPrinter::print_file].
+    fn write_file(&self, out: &mut impl Write, file: &FileMatches) -> Result<()> {
+        let owners = file.owners.as_slice();
+        let mut stats = Stats {
+            searches: 1,
+            searches_with_match: u64::from(!file.is_empty()),
+            bytes_searched: file.as_bytes().len() as u64,
+            matches: file.len() as u64,
+            ..Stats::default()
+        };
+
+        if file.is_empty() {
+            self.totals.lock().unwrap().add(&stats);
+            return Ok(());
+        }
+
+        let mut buffer = vec![];
+        write_message(&mut buffer, &Message::Begin { path: Data::from_path(&file.path), owners })?;
+
+        for message in match_messages(file, owners) {
+            write_message(&mut buffer, &message)?;
+            stats.matched_lines += 1;
+        }
+
+        stats.bytes_printed = buffer.len() as u64;
+        self.totals.lock().unwrap().add(&stats);
+
+        let end = Message::End { path: Data::from_path(&file.path), binary_offset: None, stats };
+        write_message(&mut buffer, &end)?;
+
+        out.write_all(&buffer)?;
+        Ok(())
+    }
+
+    /// Write the `summary` message of the whole search to `out`.
+    fn write_summary(self, out: &mut impl Write) -> Result<()> {
+        let elapsed = self.start.elapsed();
+        let mut stats = self.totals.into_inner().unwrap();
+        stats.elapsed = elapsed.into();
+
+        let summary = Message::Summary { elapsed_total: elapsed.into(), stats };
+        write_message(out, &summary)
+    }
+}
+
+/// Group the matches of a file into `match` messages, one for each set of
+/// contiguous lines that contain matches.
+fn match_messages<'a>(file: &'a FileMatches, owners: &'a [String]) -> Vec<Message<'a>> {
+    let contents = file.as_bytes();
+    let index = LineIndex::from_bytes(contents);
+
+    // The lines of a match include their terminator.
+    let line_bounds = |pos: usize| {
+        let (line, start, end) = index.line_range(pos);
+        (line, start, if end < contents.len() { end + 1 } else { end })
+    };
+
+    let mut messages = vec![];
+    let mut current: Option<(usize, usize, usize, Vec<SubMatch<'a>>)> = None;
+
+    for m in &file.matches {
+        let (line, start, _) = line_bounds(m.start);
+        let (_, _, end) = line_bounds(m.end.saturating_sub(1).max(m.start));
+
+        match &mut current {
+            Some((_, block_start, block_end, submatches)) if m.start < *block_end => {
+                *block_end = (*block_end).max(end);
+                submatches.push(SubMatch {
+                    matched: Data::from_bytes(&contents[m.start..m.end]),
+                    start: m.start - *block_start,
+                    end: m.end - *block_start,
+                });
+            }
+            _ => {
+                let submatches = vec![SubMatch {
+                    matched: Data::from_bytes(&contents[m.start..m.end]),
+                    start: m.start - start,
+                    end: m.end - start,
+                }];
+
+                if let Some(previous) = current.replace((line, start, end, submatches)) {
+                    messages.push(previous);
+                }
+            }
+        }
+    }
+
+    messages.extend(current);
+    messages
+        .into_iter()
+        .map(|(line_number, start, end, submatches)| Message::Match {
+            path: Data::from_path(&file.path),
+            lines: Data::from_bytes(&contents[start..end]),
+            line_number,
+            absolute_offset: start,
+            submatches,
+            owners,
+        })
+        .collect()
+}
+
+/// Write a single message, followed by a newline.
+fn write_message(out: &mut impl Write, message: &Message<'_>) -> Result<()> {
+    serde_json::to_writer(&mut *out, message)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::{Value, json};
+    use teamsearch_matcher::{Pattern, SearchOptions, search_bytes};
+
+    use super::*;
+
+    #[test]
+    fn test_messages() {
+        let search = |path: &str, contents: &[u8]| {
+            let options = SearchOptions { invalid_utf8: true, ..SearchOptions::default() };
+            let pattern = Pattern::new("foo", false);
+            let mut file = search_bytes(pattern, options, PathBuf::from(path), contents.to_vec());
+            file.as_mut().unwrap().owners = vec!["@org/core".to_string()];
+            file.unwrap()
+        };
+
+        let printer = RipgrepPrinter::new(Instant::now());
+        let mut out = vec![];
+        printer.write_file(&mut out, &search("a.rs", b"foo bar\nbaz\nfoo \xff foo\n")).unwrap();
+        printer.write_file(&mut out, &search("b.rs", b"bar\n")).unwrap();
+        let printed = out.len();
+        printer.write_summary(&mut out).unwrap();
+
+        // Every line is a message of its own.
+        let text = String::from_utf8(out).unwrap();
+        let mut messages = text.lines().map(|line| serde_json::from_str::<Value>(line).unwrap());
+        let mut next = || messages.next().unwrap();
+
+        let owners = json!(["@org/core"]);
+        assert_eq!(
+            next(),
+            json!({"type": "begin", "data": {"path": {"text": "a.rs"}, "owners": owners}})
+        );
+        assert_eq!(
+            next(),
+            json!({"type": "match", "data": {
+                "path": {"text": "a.rs"},
+                "lines": {"text": "foo bar\n"},
+                "line_number": 1,
+                "absolute_offset": 0,
+                "submatches": [{"match": {"text": "foo"}, "start": 0, "end": 3}],
+                "owners": owners,
+            }})
+        );
+
+        // The line that isn't valid UTF-8 is reported as bytes, but each of
+        // the matches within it is still valid UTF-8.
+        assert_eq!(
+            next(),
+            json!({"type": "match", "data": {
+                "path": {"text": "a.rs"},
+                "lines": {"bytes": BASE64_STANDARD.encode(b"foo \xff foo\n")},
+                "line_number": 3,
+                "absolute_offset": 12,
+                "submatches": [
+                    {"match": {"text": "foo"}, "start": 0, "end": 3},
+                    {"match": {"text": "foo"}, "start": 6, "end": 9},
+                ],
+                "owners": owners,
+            }})
+        );
+
+        let end = next();
+        assert_eq!(end["type"], "end");
+        assert_eq!(end["data"]["path"], json!({"text": "a.rs"}));
+        assert_eq!(end["data"]["binary_offset"], Value::Null);
+
+        let stats = &end["data"]["stats"];
+        assert_eq!(stats["searches"], 1);
+        assert_eq!(stats["searches_with_match"], 1);
+        assert_eq!(stats["bytes_searched"], 22);
+        assert_eq!(stats["matched_lines"], 2);
+        assert_eq!(stats["matches"], 3);
+
+        // The file without any matches only counts towards the summary.
+        let summary = next();
+        assert_eq!(summary["type"], "summary");
+
+        let totals = &summary["data"]["stats"];
+        assert_eq!(totals["searches"], 2);
+        assert_eq!(totals["searches_with_match"], 1);
+        assert_eq!(totals["bytes_searched"], 26);
+        assert_eq!(totals["matched_lines"], 2);
+        assert_eq!(totals["matches"], 3);
+        assert_eq!(totals["bytes_printed"], stats["bytes_printed"]);
+        assert!(stats["bytes_printed"].as_u64().unwrap() < printed as u64);
+        for elapsed in [&summary["data"]["elapsed_total"], &totals["elapsed"]] {
+            assert!(
+                elapsed["secs"].is_u64()
+                    && elapsed["nanos"].is_u64()
+                    && elapsed["human"].is_string()
+            );
+        }
+    }
+}
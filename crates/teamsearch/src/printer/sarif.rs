@@ -0,0 +1,334 @@
+//! A printer that emits the results of `find` and `orphans` as a [SARIF 2.1]
+//! log, so that they can be ingested by code scanning dashboards alongside
+//! the results of other static analysis tools.
+//!
+//! Each match (or orphaned file) becomes a single result, with a physical
+//! location that points at the file and a region within it. The teams that own
+//! the file are recorded in the `owners` property of the result.
+//!
+//! [SARIF 2.1]: https://docs.oasis-open.org/sarif/sarif/v2.1.0/sarif-v2.1.0.html
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::Result;
+use itertools::Itertools;
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde::Serialize;
+use teamsearch_matcher::{FileMatches, Match};
+use teamsearch_utils::lines::LineIndex;
+
+use crate::{
+    commands::{find::FindResult, orphans::OrphanResult},
+    version,
+};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// The base that the URIs of all of the artifacts are relative to.
+const SRCROOT: &str = "%SRCROOT%";
+
+/// The characters that are escaped within the segments of an artifact URI.
+const URI_SEGMENT: &AsciiSet =
+    &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Log {
+    #[serde(rename = "$schema")]
+    schema: &'static str,
+    version: &'static str,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Run {
+    tool: Tool,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    original_uri_base_ids: BTreeMap<&'static str, ArtifactLocation>,
+    column_kind: &'static str,
+    results: Vec<SarifResult>,
+}
+
+#[derive(Serialize)]
+struct Tool {
+    driver: Driver,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Driver {
+    name: &'static str,
+    version: String,
+    semantic_version: &'static str,
+    information_uri: &'static str,
+    rules: Vec<Rule>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rule {
+    id: &'static str,
+    short_description: Message,
+}
+
+#[derive(Serialize)]
+struct Message {
+    text: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SarifResult {
+    rule_id: &'static str,
+    level: &'static str,
+    message: Message,
+    locations: Vec<Location>,
+    properties: Properties,
+}
+
+#[derive(Serialize)]
+struct Properties {
+    owners: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Location {
+    physical_location: PhysicalLocation,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PhysicalLocation {
+    artifact_location: ArtifactLocation,
+    region: Region,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactLocation {
+    uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri_base_id: Option<&'static str>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Region {
+    start_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start_column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_line: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_column: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    snippet: Option<Message>,
+}
+
+/// Builds a SARIF log with a single run, resolving the paths of the results
+/// relative to the current working directory.
+struct LogBuilder {
+    cwd: Option<std::path::PathBuf>,
+    rule: Rule,
+    results: Vec<SarifResult>,
+}
+
+impl LogBuilder {
+    fn new(rule: Rule) -> Self {
+        LogBuilder { cwd: std::env::current_dir().ok(), rule, results: vec![] }
+    }
+
+    /// Convert a path into the location of an artifact. Paths within the
+    /// current working directory are relative to [SRCROOT], and any other
+    /// paths are absolute `file` URIs.
+    fn artifact_location(&self, path: &Path) -> ArtifactLocation {
+        match self.cwd.as_deref().and_then(|cwd| path.strip_prefix(cwd).ok()) {
+            Some(relative) => {
+                ArtifactLocation { uri: encode_path(relative), uri_base_id: Some(SRCROOT) }
+            }
+            None => {
+                ArtifactLocation { uri: format!("file://{}", encode_path(path)), uri_base_id: None }
+            }
+        }
+    }
+
+    fn push(&mut self, path: &Path, region: Region, message: String, owners: Vec<String>) {
+        let physical_location =
+            PhysicalLocation { artifact_location: self.artifact_location(path), region };
+
+        self.results.push(SarifResult {
+            rule_id: self.rule.id,
+            level: "warning",
+            message: Message { text: message },
+            locations: vec![Location { physical_location }],
+            properties: Properties { owners },
+        });
+    }
+
+    fn build(self) -> Log {
+        let mut original_uri_base_ids = BTreeMap::new();
+        if let Some(cwd) = &self.cwd {
+            // The base must end with a slash for the relative URIs to resolve
+            // against it.
+            let uri = format!("file://{}/", encode_path(cwd).trim_end_matches('/'));
+            original_uri_base_ids.insert(SRCROOT, ArtifactLocation { uri, uri_base_id: None });
+        }
+
+        Log {
+            schema: SCHEMA,
+            version: "2.1.0",
+            runs: vec![Run {
+                tool: Tool {
+                    driver: Driver {
+                        name: "teamsearch",
+                        version: version::version().to_string(),
+                        semantic_version: env!("CARGO_PKG_VERSION"),
+                        information_uri: "https://github.com/feds01/teamsearch",
+                        rules: vec![self.rule],
+                    },
+                },
+                original_uri_base_ids,
+                column_kind: "utf16CodeUnits",
+                results: self.results,
+            }],
+        }
+    }
+
+    fn print(self) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(&self.build())?);
+        Ok(())
+    }
+}
+
+/// Print the matches of a search as a SARIF log.
+pub(crate) fn print_find(result: &FindResult, pattern: &str) -> Result<()> {
+    find_log(result, pattern).print()
+}
+
+/// Build the log of the matches of a search, see [print_find].
+fn find_log(result: &FindResult, pattern: &str) -> LogBuilder {
+    let mut builder = LogBuilder::new(Rule {
+        id: "teamsearch/match",
+        short_description: Message { text: format!("Matches of the pattern `{pattern}`.") },
+    });
+
+    for file in &result.file_matches {
+        let owners = &file.owners;
+        let message = match owners.as_slice() {
+            [] => format!("Match of `{pattern}` in a file that isn't owned by any team."),
+            owners => format!("Match of `{pattern}` in a file owned by {}.", owners.join(", ")),
+        };
+
+        let index = LineIndex::new(&file.contents);
+        for m in &file.matches {
+            builder.push(
+                &file.path,
+                match_region(file, &index, *m),
+                message.clone(),
+                owners.clone(),
+            );
+        }
+    }
+
+    builder
+}
+
+/// Print the orphaned files as a SARIF log, each result points at the start
+/// of the file.
+pub(crate) fn print_orphans(result: &OrphanResult) -> Result<()> {
+    let mut builder = LogBuilder::new(Rule {
+        id: "teamsearch/orphan",
+        short_description: Message { text: "Files that aren't owned by any team.".to_string() },
+    });
+
+    for file in &result.orphans {
+        let region = Region { start_line: 1, ..Region::default() };
+        builder.push(file.path(), region, "File isn't owned by any team.".to_string(), vec![]);
+    }
+
+    builder.print()
+}
+
+/// Compute the region of a match, the columns are counted in UTF-16 code
+/// units and the end column is exclusive.
+fn match_region(file: &FileMatches, index: &LineIndex, m: Match) -> Region {
+    let contents = file.contents.as_str();
+    let position = |pos: usize| {
+        let (line, start, _) = index.line_range(pos);
+        (line, contents[start..pos].encode_utf16().count() + 1)
+    };
+
+    let (start_line, start_column) = position(m.start);
+    let (end_line, end_column) = position(m.end);
+
+    Region {
+        start_line,
+        start_column: Some(start_column),
+        end_line: Some(end_line),
+        end_column: Some(end_column),
+        snippet: Some(Message { text: contents[m.start..m.end].to_string() }),
+    }
+}
+
+/// Percent-encode each of the segments of a path, joining them with `/`.
+fn encode_path(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            std::path::Component::RootDir => Some(String::new()),
+            std::path::Component::Normal(segment) => {
+                Some(utf8_percent_encode(&segment.to_string_lossy(), URI_SEGMENT).to_string())
+            }
+            _ => None,
+        })
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+    use teamsearch_matcher::{Pattern, SearchOptions, search_contents};
+
+    use super::*;
+
+    #[test]
+    fn test_find_log() {
+        let cwd = std::env::current_dir().unwrap();
+        let contents = "let é = foo;\n𝒳 foo\n".to_string();
+        let pattern = Pattern::new("foo", false);
+        let path = cwd.join("src/my file.rs");
+        let mut file = search_contents(pattern, SearchOptions::default(), path, contents).unwrap();
+        file.owners = vec!["@org/core".to_string()];
+
+        let result = FindResult { file_matches: vec![file], ..FindResult::default() };
+        let log = serde_json::to_value(find_log(&result, "foo").build()).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "utf16CodeUnits");
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "teamsearch/match");
+        assert!(run["originalUriBaseIds"]["%SRCROOT%"]["uri"].as_str().unwrap().ends_with('/'));
+
+        // The columns are 1-based and counted in UTF-16 code units, where the
+        // `é` is a single unit and the `𝒳` is two.
+        let results = run["results"].as_array().unwrap();
+        let regions = results.iter().map(|result| {
+            let location = &result["locations"][0]["physicalLocation"];
+            assert_eq!(
+                location["artifactLocation"],
+                json!({"uri": "src/my%20file.rs", "uriBaseId": "%SRCROOT%"})
+            );
+            assert_eq!(result["properties"], json!({"owners": ["@org/core"]}));
+            assert_eq!(result["ruleId"], "teamsearch/match");
+            location["region"].clone()
+        });
+        assert_eq!(
+            regions.collect::<Vec<Value>>(),
+            [
+                json!({"startLine": 1, "startColumn": 9, "endLine": 1, "endColumn": 12, "snippet": {"text": "foo"}}),
+                json!({"startLine": 2, "startColumn": 4, "endLine": 2, "endColumn": 7, "snippet": {"text": "foo"}}),
+            ]
+        );
+    }
+}
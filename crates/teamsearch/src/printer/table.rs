@@ -0,0 +1,43 @@
+//! A printer for the tabular output formats, i.e. CSV and TSV, which are
+//! useful for pulling the results into a spreadsheet. Fields are quoted
+//! whenever they contain the delimiter, a quote or a newline.
+
+use std::io::{self, Stdout};
+
+use anyhow::Result;
+
+use crate::cli::OutputFormat;
+
+pub(crate) struct TablePrinter {
+    writer: csv::Writer<Stdout>,
+}
+
+impl TablePrinter {
+    /// Create a printer for the given format, and print the header row.
+    pub(crate) fn new(format: OutputFormat, header: &[&str]) -> Result<Self> {
+        let delimiter = match format {
+            OutputFormat::Tsv => b'\t',
+            _ => b',',
+        };
+
+        let mut writer = csv::WriterBuilder::new().delimiter(delimiter).from_writer(io::stdout());
+        writer.write_record(header)?;
+        Ok(TablePrinter { writer })
+    }
+
+    /// Print a single row of the table.
+    pub(crate) fn row<I, T>(&mut self, record: I) -> Result<()>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.writer.write_record(record)?;
+        Ok(())
+    }
+
+    /// Flush all of the rows that haven't been printed yet.
+    pub(crate) fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
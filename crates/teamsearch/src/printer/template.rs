@@ -0,0 +1,212 @@
+//! A printer that formats each match of `find` using a user provided template,
+//! which is useful for ad-hoc scripting and for loading the results into the
+//! quickfix list of an editor, i.e. `--vimgrep`. Templates consist of text
+//! and placeholders, such as `{path}:{line}:{match}`. Literal braces can be
+//! written as `{{` and `}}`.
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::Result;
+use teamsearch_matcher::FileMatches;
+use teamsearch_utils::lines::LineIndex;
+
+/// The template that is used by `--vimgrep`.
+const VIMGREP: &str = "{path}:{line}:{column}:{line_text}";
+
+/// A value of a match that can be substituted into a template.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// The path of the file, as it was searched.
+    Path,
+
+    /// The path of the file, relative to the current working directory.
+    RelativePath,
+
+    /// The line number of the start of the match, starting at 1.
+    Line,
+
+    /// The column of the start of the match in bytes, starting at 1.
+    Column,
+
+    /// The byte offset of the start of the match within the file.
+    Start,
+
+    /// The byte offset of the end of the match within the file.
+    End,
+
+    /// The text of the match.
+    Match,
+
+    /// The text of the line that contains the start of the match, without
+    /// the line terminator.
+    LineText,
+
+    /// The teams that own the file, separated by commas.
+    Owners,
+}
+
+impl Placeholder {
+    const ALL: &[(&str, Placeholder)] = &[
+        ("path", Placeholder::Path),
+        ("relpath", Placeholder::RelativePath),
+        ("line", Placeholder::Line),
+        ("column", Placeholder::Column),
+        ("start", Placeholder::Start),
+        ("end", Placeholder::End),
+        ("match", Placeholder::Match),
+        ("line_text", Placeholder::LineText),
+        ("owners", Placeholder::Owners),
+    ];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder `{{{name}`")),
+                        }
+                    }
+
+                    let Some((_, placeholder)) = Placeholder::ALL.iter().find(|(n, _)| *n == name)
+                    else {
+                        let known = Placeholder::ALL.iter().map(|(n, _)| format!("{{{n}}}"));
+                        return Err(format!(
+                            "unrecognized placeholder `{{{name}}}`, expected one of {}",
+                            known.collect::<Vec<_>>().join(", ")
+                        ));
+                    };
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+
+                    parts.push(Part::Placeholder(*placeholder));
+                }
+                '}' => return Err("unmatched `}`, literal braces must be written as `}}`".into()),
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Template { parts })
+    }
+}
+
+impl Template {
+    /// The template that is used by `--vimgrep`.
+    pub(crate) fn vimgrep() -> Self {
+        VIMGREP.parse().expect("the vimgrep template is valid")
+    }
+
+    /// Print each of the matches of the given files on a separate line.
+    pub(crate) fn print(&self, files: &[FileMatches]) -> Result<()> {
+        let cwd = std::env::current_dir().ok();
+        let mut out = BufWriter::new(io::stdout().lock());
+        let mut line = String::new();
+
+        for file in files {
+            let index = LineIndex::new(&file.contents);
+            let relative_path = cwd
+                .as_deref()
+                .and_then(|cwd| file.path.strip_prefix(cwd).ok())
+                .unwrap_or(&file.path);
+
+            for m in &file.matches {
+                let (line_number, line_start, line_end) = index.line_range(m.start);
+
+                line.clear();
+                for part in &self.parts {
+                    match part {
+                        Part::Text(text) => line.push_str(text),
+                        Part::Placeholder(placeholder) => match placeholder {
+                            Placeholder::Path => push_path(&mut line, &file.path),
+                            Placeholder::RelativePath => push_path(&mut line, relative_path),
+                            Placeholder::Line => write!(line, "{line_number}")?,
+                            Placeholder::Column => write!(line, "{}", m.start - line_start + 1)?,
+                            Placeholder::Start => write!(line, "{}", m.start)?,
+                            Placeholder::End => write!(line, "{}", m.end)?,
+                            Placeholder::Match => line.push_str(&file.contents[m.start..m.end]),
+                            Placeholder::LineText => line.push_str(
+                                file.contents[line_start..line_end].trim_end_matches('\r'),
+                            ),
+                            Placeholder::Owners => line.push_str(&file.owners.join(",")),
+                        },
+                    }
+                }
+
+                writeln!(out, "{line}")?;
+            }
+        }
+
+        out.flush()?;
+        Ok(())
+    }
+}
+
+fn push_path(line: &mut String, path: &Path) {
+    line.push_str(&path.to_string_lossy());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_template() {
+        let template = "{{{path}}}:{line}".parse::<Template>().unwrap();
+        assert_eq!(
+            template.parts,
+            vec![
+                Part::Text("{".into()),
+                Part::Placeholder(Placeholder::Path),
+                Part::Text("}:".into()),
+                Part::Placeholder(Placeholder::Line),
+            ]
+        );
+
+        assert!(VIMGREP.parse::<Template>().is_ok());
+        assert!("{path}:{lines}".parse::<Template>().is_err());
+        assert!("{path}}".parse::<Template>().is_err());
+        assert!("{path".parse::<Template>().is_err());
+    }
+}
@@ -6,13 +6,17 @@
 //! remain to be somewhat implementation independent when its looking for
 //! patterns within code.
 
+pub mod replace;
+
 use std::{fs::File, io::Read, path::PathBuf};
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use derive_more::Constructor;
-use grep_matcher::Matcher;
+use grep_matcher::{Captures, Matcher};
 use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use regex_syntax::hir::{Hir, HirKind};
 use serde::{self, Serialize, ser::SerializeStruct};
+use teamsearch_utils::lines::LineIndex;
 
 /// A match that was found within a file. This describes the
 /// `range` of the match.
@@ -25,19 +29,61 @@ pub struct Match {
     pub end: usize,
 }
 
-/// Internal structure used to report what `teamsearch` found with reference
-/// to the `snippet` that was matched.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Constructor, Serialize)]
-struct MatchSnippet<'s> {
+/// Structure used to report what `teamsearch` found with reference to the
+/// `snippet` that was matched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MatchSnippet<'s> {
     /// The start of the match.
     start: usize,
 
     /// The end of the match.
     end: usize,
 
+    /// The line on which the match starts (1-indexed).
+    line: usize,
+
+    /// The column at which the match starts, counted in bytes (1-indexed).
+    column: usize,
+
+    /// The column at which the match starts, counted in UTF-16 code units
+    /// (1-indexed). This is what most editors expect.
+    column_utf16: usize,
+
     /// The text that was matched when scanning.
     #[serde(rename = "match")]
     snippet: &'s str,
+
+    /// The full text of the line on which the match starts, excluding the
+    /// line terminator.
+    line_text: &'s str,
+
+    /// The text that the match would be replaced with, if a replacement
+    /// was requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replacement: Option<&'s str>,
+
+    /// The capture groups of the pattern that participated in the match.
+    captures: Vec<CaptureSnippet<'s>>,
+}
+
+/// Internal structure used to report a capture group of a [MatchSnippet].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+struct CaptureSnippet<'s> {
+    /// The index of the group within the pattern, starting at `1`.
+    index: usize,
+
+    /// The name of the group, if it is a named group.
+    name: Option<&'s str>,
+
+    /// The start of the group.
+    start: usize,
+
+    /// The end of the group.
+    end: usize,
+
+    /// The text that was captured by the group.
+    #[serde(rename = "match")]
+    snippet: &'s str,
 }
 
 /// The result of searching a file for matches.
@@ -52,6 +98,31 @@ pub struct FileMatches {
 
     /// The matches that were found within the file.
     pub matches: Vec<Match>,
+
+    /// The replacement text for each of the `matches`, in the same order. This
+    /// is empty if no replacement was requested.
+    pub replacements: Vec<String>,
+
+    /// The capture groups of each of the `matches`, in the same order. The
+    /// groups of a match are indexed by their position in the pattern, with
+    /// the whole match omitted, i.e. `groups[i][0]` is the first group of
+    /// the `i`-th match. A group is `None` if it didn't participate in the
+    /// match.
+    pub groups: Vec<Vec<Option<Match>>>,
+
+    /// The names of the capture groups of the pattern, indexed in the same way
+    /// as the `groups` of a match.
+    pub group_names: Vec<Option<String>>,
+
+    /// The teams that own the file. The matcher doesn't know anything about
+    /// the CODEOWNERS file, so this is left empty by [search_file] and is
+    /// filled in by the caller.
+    pub owners: Vec<String>,
+
+    /// The raw contents of the file if they aren't valid UTF-8, which are only
+    /// searched if [SearchOptions::invalid_utf8] is set. The `contents` are
+    /// empty in that case, and the matches refer to these bytes instead.
+    pub bytes: Option<Vec<u8>>,
 }
 
 impl Serialize for FileMatches {
@@ -59,8 +130,9 @@ impl Serialize for FileMatches {
     where
         S: serde::Serializer,
     {
-        let mut item = serializer.serialize_struct("FileMatches", 2)?;
+        let mut item = serializer.serialize_struct("FileMatches", 3)?;
         item.serialize_field("path", &self.path)?;
+        item.serialize_field("owners", &self.owners)?;
         item.serialize_field("matches", &self.snippets())?;
         item.end()
     }
@@ -77,20 +149,77 @@ impl FileMatches {
         self.matches.is_empty()
     }
 
-    fn snippets(&self) -> Vec<MatchSnippet<'_>> {
+    /// Get the contents of the file as bytes, whether or not they're valid
+    /// UTF-8.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_deref().unwrap_or(self.contents.as_bytes())
+    }
+
+    /// Only keep the first `len` matches of the file.
+    pub fn truncate(&mut self, len: usize) {
+        self.matches.truncate(len);
+        self.replacements.truncate(len);
+        self.groups.truncate(len);
+    }
+
+    /// Get the replacement for the match at the given index, if any.
+    pub fn replacement(&self, index: usize) -> Option<&str> {
+        self.replacements.get(index).map(String::as_str)
+    }
+
+    /// Get the capture groups of the match at the given index.
+    pub fn groups(&self, index: usize) -> &[Option<Match>] {
+        self.groups.get(index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get a [MatchSnippet] for each of the matches, which describes where
+    /// the match is located within the file and what it matched.
+    pub fn snippets(&self) -> Vec<MatchSnippet<'_>> {
+        let lines = LineIndex::new(&self.contents);
+
         self.matches
             .iter()
-            .map(|m| MatchSnippet {
-                start: m.start,
-                end: m.end,
-                snippet: &self.contents[m.start..m.end],
+            .enumerate()
+            .map(|(index, m)| {
+                let (line, line_start, line_end) = lines.line_range(m.start);
+                let prefix = &self.contents[line_start..m.start];
+
+                MatchSnippet {
+                    start: m.start,
+                    end: m.end,
+                    line,
+                    column: prefix.len() + 1,
+                    column_utf16: prefix.encode_utf16().count() + 1,
+                    snippet: &self.contents[m.start..m.end],
+                    line_text: self.contents[line_start..line_end].trim_end_matches('\r'),
+                    replacement: self.replacement(index),
+                    captures: self.capture_snippets(index),
+                }
+            })
+            .collect()
+    }
+
+    fn capture_snippets(&self, index: usize) -> Vec<CaptureSnippet<'_>> {
+        self.groups(index)
+            .iter()
+            .enumerate()
+            .filter_map(|(group, m)| {
+                let m = m.as_ref()?;
+
+                Some(CaptureSnippet {
+                    index: group + 1,
+                    name: self.group_names.get(group).and_then(|name| name.as_deref()),
+                    start: m.start,
+                    end: m.end,
+                    snippet: &self.contents[m.start..m.end],
+                })
             })
             .collect()
     }
 }
 
 /// A pattern that is used to search for matches within a file.
-#[derive(Constructor)]
+#[derive(Debug, Clone, Copy, Constructor)]
 pub struct Pattern<'s> {
     /// The pattern to search for.
     pub pattern: &'s str,
@@ -99,35 +228,329 @@ pub struct Pattern<'s> {
     pub case_insensitive: bool,
 }
 
-/// Perform a scan for a `pattern` of a given file, specified with a [PathBuf].
-pub fn search_file(pattern: Pattern<'_>, path: PathBuf) -> Result<FileMatches> {
-    let Pattern { pattern, case_insensitive } = pattern;
+/// Options that control how the contents of a file are searched.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions<'s> {
+    /// Report the lines that do *not* match the pattern instead of the
+    /// matches themselves. Each reported [Match] spans an entire line,
+    /// excluding the line terminator.
+    pub invert_match: bool,
 
-    let matcher = RegexMatcherBuilder::new().case_insensitive(case_insensitive).build(pattern)?;
+    /// Stop searching the file once this many matches have been found.
+    pub max_count: Option<usize>,
+
+    /// A replacement template for each of the matches. The template may
+    /// refer to capture groups of the pattern using `$1` or `${name}`.
+    pub replacement: Option<&'s str>,
+
+    /// Search the files that aren't valid UTF-8 as raw bytes rather than
+    /// failing, see [FileMatches::bytes]. This should only be set by callers
+    /// that look at the raw bytes, since the `contents` of such files are
+    /// empty.
+    pub invalid_utf8: bool,
+}
 
+/// Perform a scan for a `pattern` of a given file, specified with a [PathBuf].
+pub fn search_file(
+    pattern: Pattern<'_>,
+    options: SearchOptions<'_>,
+    path: PathBuf,
+) -> Result<FileMatches> {
     // Load the file contents.
     let contents = {
-        let mut contents = String::new();
-        File::open(&path)?.read_to_string(&mut contents)?;
+        let mut contents = Vec::new();
+        File::open(&path)?.read_to_end(&mut contents)?;
         contents
     };
 
-    let matches = find_matches(&matcher, &contents)?;
-    Ok(FileMatches { path, contents, matches })
+    search_bytes(pattern, options, path, contents)
+}
+
+/// Perform a scan for a `pattern` of the raw `contents` of a file that have
+/// already been loaded. Unless [SearchOptions::invalid_utf8] is set, the
+/// contents must be valid UTF-8.
+pub fn search_bytes(
+    pattern: Pattern<'_>,
+    options: SearchOptions<'_>,
+    path: PathBuf,
+    contents: Vec<u8>,
+) -> Result<FileMatches> {
+    let bytes = match String::from_utf8(contents) {
+        Ok(contents) => return search_contents(pattern, options, path, contents),
+        Err(_) if !options.invalid_utf8 => {
+            return Err(anyhow!("`{}` isn't valid UTF-8.", path.display()));
+        }
+        Err(err) => err.into_bytes(),
+    };
+
+    let matcher = build_matcher(pattern)?;
+    let mut file = search_haystack(&matcher, pattern, options, &bytes)?;
+    file.path = path;
+    file.bytes = Some(bytes);
+    Ok(file)
+}
+
+/// Perform a scan for a `pattern` of the `contents` of a file that have
+/// already been loaded, e.g. from a git revision rather than the file system.
+pub fn search_contents(
+    pattern: Pattern<'_>,
+    options: SearchOptions<'_>,
+    path: PathBuf,
+    contents: String,
+) -> Result<FileMatches> {
+    let matcher = build_matcher(pattern)?;
+    let file = search_haystack(&matcher, pattern, options, contents.as_bytes())?;
+    Ok(FileMatches { path, contents, ..file })
+}
+
+/// Build the matcher for the given `pattern`.
+fn build_matcher(pattern: Pattern<'_>) -> Result<RegexMatcher> {
+    let Pattern { pattern, case_insensitive } = pattern;
+    Ok(RegexMatcherBuilder::new().case_insensitive(case_insensitive).build(pattern)?)
+}
+
+/// Search the `haystack` for matches, leaving the `path` and the contents of
+/// the resulting [FileMatches] for the caller to fill in.
+fn search_haystack(
+    matcher: &RegexMatcher,
+    pattern: Pattern<'_>,
+    options: SearchOptions<'_>,
+    haystack: &[u8],
+) -> Result<FileMatches> {
+    if options.invert_match {
+        let FoundMatches { matches, .. } = find_matches(matcher, haystack, None, None)?;
+        let matches = invert_matches(&matches, haystack, options.max_count);
+        return Ok(FileMatches { matches, ..FileMatches::default() });
+    }
+
+    let FoundMatches { matches, replacements, groups } =
+        find_matches(matcher, haystack, options.max_count, options.replacement)?;
+    let group_names = group_names(pattern.pattern);
+
+    Ok(FileMatches { matches, replacements, groups, group_names, ..FileMatches::default() })
+}
+
+/// The matches that were found by [find_matches].
+#[derive(Default)]
+struct FoundMatches {
+    /// The matches themselves.
+    matches: Vec<Match>,
+
+    /// The expanded replacement for each match, if a template was provided.
+    replacements: Vec<String>,
+
+    /// The capture groups of each match, excluding the whole match.
+    groups: Vec<Vec<Option<Match>>>,
 }
 
 /// Find matches in a file.
 ///
 ///
 /// This function will find all matches in a given file, and then return them
-/// as a list of [Match]s.
-fn find_matches(matcher: &RegexMatcher, contents: &str) -> Result<Vec<Match>> {
-    let mut matches = Vec::new();
+/// as a list of [Match]s alongside their capture groups. If a `limit` is
+/// provided, the search stops as soon as that many matches have been found.
+/// If a `replacement` template is provided, it is expanded for each match
+/// using the capture groups of that match.
+fn find_matches(
+    matcher: &RegexMatcher,
+    haystack: &[u8],
+    limit: Option<usize>,
+    replacement: Option<&str>,
+) -> Result<FoundMatches> {
+    let mut found = FoundMatches::default();
+
+    if limit == Some(0) {
+        return Ok(found);
+    }
+
+    let mut captures = matcher.new_captures()?;
+    let mut buffer = Vec::new();
 
-    let _ = matcher.try_find_iter::<_, std::io::Error>(contents.as_bytes(), |m| {
-        matches.push(Match { start: m.start(), end: m.end() });
-        Ok(true)
+    matcher.captures_iter(haystack, &mut captures, |captures| {
+        let to_match = |m: grep_matcher::Match| Match { start: m.start(), end: m.end() };
+
+        found.matches.push(to_match(captures.get(0).unwrap()));
+        found.groups.push((1..captures.len()).map(|i| captures.get(i).map(to_match)).collect());
+
+        if let Some(replacement) = replacement {
+            buffer.clear();
+            captures.interpolate(
+                |name| matcher.capture_index(name),
+                haystack,
+                replacement.as_bytes(),
+                &mut buffer,
+            );
+            found.replacements.push(String::from_utf8_lossy(&buffer).into_owned());
+        }
+
+        limit.is_none_or(|limit| found.matches.len() < limit)
     })?;
 
-    Ok(matches)
+    Ok(found)
+}
+
+/// Compute the names of the capture groups of the given `pattern`, indexed by
+/// the position of the group minus one (the whole match is omitted).
+fn group_names(pattern: &str) -> Vec<Option<String>> {
+    fn visit(hir: &Hir, names: &mut Vec<Option<String>>) {
+        match hir.kind() {
+            HirKind::Capture(capture) => {
+                let index = capture.index as usize - 1;
+                if names.len() <= index {
+                    names.resize(index + 1, None);
+                }
+
+                names[index] = capture.name.as_deref().map(str::to_string);
+                visit(&capture.sub, names);
+            }
+            HirKind::Repetition(repetition) => visit(&repetition.sub, names),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+                hirs.iter().for_each(|hir| visit(hir, names))
+            }
+            _ => {}
+        }
+    }
+
+    let mut names = Vec::new();
+
+    // The pattern has already been compiled by the matcher, so this should
+    // never fail, but we'd rather report unnamed groups than an error.
+    if let Ok(hir) = regex_syntax::parse(pattern) {
+        visit(&hir, &mut names);
+    }
+
+    names
+}
+
+/// Compute the lines of `contents` that are not touched by any of the given
+/// `matches`. Each line is returned as a [Match] that spans the line without
+/// its terminator.
+fn invert_matches(matches: &[Match], contents: &[u8], limit: Option<usize>) -> Vec<Match> {
+    let mut inverted = Vec::new();
+    let mut matches = matches.iter().peekable();
+    let mut line_start = 0;
+
+    for line in contents.split_inclusive(|byte| *byte == b'\n') {
+        let line_end = line_start + line.len();
+
+        // Skip past any matches that end before this line, and then check
+        // whether the next match begins on this line.
+        while matches.next_if(|m| m.end.max(m.start + 1) <= line_start).is_some() {}
+        let is_match = matches.peek().is_some_and(|m| m.start < line_end);
+
+        if !is_match {
+            if limit.is_some_and(|limit| inverted.len() >= limit) {
+                break;
+            }
+
+            let terminator = line.iter().rev().take_while(|b| matches!(b, b'\n' | b'\r')).count();
+            let content_end = line_end - terminator;
+            inverted.push(Match { start: line_start, end: content_end });
+        }
+
+        line_start = line_end;
+    }
+
+    inverted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str, contents: &str, limit: Option<usize>) -> Vec<Match> {
+        let matcher = RegexMatcherBuilder::new().build(pattern).unwrap();
+        find_matches(&matcher, contents.as_bytes(), limit, None).unwrap().matches
+    }
+
+    #[test]
+    fn test_find_matches_limit() {
+        let contents = "foo\nbar foo\nfoo\n";
+
+        assert_eq!(search("foo", contents, None).len(), 3);
+        assert_eq!(search("foo", contents, Some(1)), vec![Match::new(0, 3)]);
+        assert!(search("foo", contents, Some(0)).is_empty());
+    }
+
+    #[test]
+    fn test_find_matches_replacements() {
+        let matcher = RegexMatcherBuilder::new().build(r"(?P<name>\w+)\((\d)\)").unwrap();
+        let contents = "foo(1) bar(2)";
+
+        let found =
+            find_matches(&matcher, contents.as_bytes(), None, Some("${name}_$2 $$")).unwrap();
+        assert_eq!(found.matches, vec![Match::new(0, 6), Match::new(7, 13)]);
+        assert_eq!(found.replacements, vec!["foo_1 $", "bar_2 $"]);
+    }
+
+    #[test]
+    fn test_find_captures() {
+        let matcher = RegexMatcherBuilder::new().build(r"(a)|(?P<name>b)").unwrap();
+
+        let found = find_matches(&matcher, b"ab", None, None).unwrap();
+        assert_eq!(found.groups[0], vec![Some(Match::new(0, 1)), None]);
+        assert_eq!(found.groups[1], vec![None, Some(Match::new(1, 2))]);
+        assert!(found.replacements.is_empty());
+    }
+
+    #[test]
+    fn test_group_names() {
+        let names = group_names(r#"isEnabled\("(?P<flag>[^"]+)"\)(,\s*(?<default>\w+))?"#);
+        assert_eq!(names, vec![Some("flag".to_string()), None, Some("default".to_string())]);
+        assert!(group_names("foo").is_empty());
+    }
+
+    #[test]
+    fn test_search_contents() {
+        let contents = "fn foo() {}\nfn Bar() {}\n".to_string();
+        let search = |pattern, case_insensitive, options| {
+            let pattern = Pattern::new(pattern, case_insensitive);
+            search_contents(pattern, options, PathBuf::from("a.rs"), contents.clone()).unwrap()
+        };
+
+        let found = search(r"fn (\w+)", false, SearchOptions::default());
+        assert_eq!(found.path, PathBuf::from("a.rs"));
+        assert_eq!(found.matches, vec![Match::new(0, 6), Match::new(12, 18)]);
+
+        let found = search("bar", true, SearchOptions { max_count: Some(1), ..Default::default() });
+        assert_eq!(found.matches, vec![Match::new(15, 18)]);
+
+        let found =
+            search("foo", false, SearchOptions { invert_match: true, ..Default::default() });
+        assert_eq!(found.matches, vec![Match::new(12, 23)]);
+
+        let options = SearchOptions { replacement: Some("fn ${1}_v2"), ..Default::default() };
+        assert_eq!(search(r"fn (\w+)", false, options).replacements, ["fn foo_v2", "fn Bar_v2"]);
+    }
+
+    #[test]
+    fn test_search_bytes() {
+        let search = |contents: &[u8], invalid_utf8| {
+            let (pattern, options) =
+                (Pattern::new("foo", false), SearchOptions { invalid_utf8, ..Default::default() });
+            search_bytes(pattern, options, PathBuf::from("a.rs"), contents.to_vec())
+        };
+
+        let found = search(b"foo\n", false).unwrap();
+        assert_eq!((found.contents.as_str(), found.bytes), ("foo\n", None));
+
+        // Contents that aren't valid UTF-8 are only searched when asked to.
+        assert!(search(b"\xff foo\n", false).is_err());
+        let found = search(b"\xff foo\n", true).unwrap();
+        assert_eq!(found.matches, vec![Match::new(2, 5)]);
+        assert_eq!((found.contents.as_str(), found.as_bytes()), ("", &b"\xff foo\n"[..]));
+    }
+
+    #[test]
+    fn test_invert_matches() {
+        let contents = "foo\nbar\r\nbaz foo\n\nqux";
+        let matches = search("foo", contents, None);
+
+        let inverted = invert_matches(&matches, contents.as_bytes(), None);
+        let lines = inverted.iter().map(|m| &contents[m.start..m.end]).collect::<Vec<_>>();
+        assert_eq!(lines, vec!["bar", "", "qux"]);
+
+        let inverted = invert_matches(&matches, contents.as_bytes(), Some(1));
+        assert_eq!(inverted, vec![Match::new(4, 7)]);
+    }
 }
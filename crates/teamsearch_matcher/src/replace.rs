@@ -0,0 +1,245 @@
+//! Utilities for applying the replacements of a [FileMatches] to the contents
+//! of the file, and for rendering the resulting changes as a unified diff.
+
+use std::{fmt::Write, ops::Range};
+
+use crate::{FileMatches, Match};
+
+/// The number of unchanged lines that are shown around each change in a
+/// unified diff.
+const CONTEXT_LINES: usize = 3;
+
+/// Apply all of the replacements of the given [FileMatches] to its contents.
+///
+/// Matches that don't have a corresponding replacement are left untouched.
+pub fn apply(file: &FileMatches) -> String {
+    apply_in(&file.contents, 0, &file.matches, &file.replacements)
+}
+
+/// Apply the `replacements` to the `matches` that fall within `contents`,
+/// where `contents` begins at the byte `offset` of the original file.
+fn apply_in(contents: &str, offset: usize, matches: &[Match], replacements: &[String]) -> String {
+    let mut result = String::with_capacity(contents.len());
+    let mut last_end = 0;
+
+    for (m, replacement) in matches.iter().zip(replacements) {
+        let (start, end) = (m.start - offset, m.end - offset);
+
+        result.push_str(&contents[last_end..start]);
+        result.push_str(replacement);
+        last_end = end;
+    }
+
+    result.push_str(&contents[last_end..]);
+    result
+}
+
+/// A contiguous set of lines that is modified by one or more replacements.
+struct Change {
+    /// The range of (0-indexed) lines that are modified.
+    lines: Range<usize>,
+
+    /// The range of the matches that fall within these lines.
+    matches: Range<usize>,
+}
+
+/// Render the replacements of the given [FileMatches] as a unified diff. The
+/// `old_path` and `new_path` are used in the diff header. If there are no
+/// replacements, an empty string is returned.
+pub fn unified_diff(file: &FileMatches, old_path: &str, new_path: &str) -> String {
+    let contents = file.contents.as_str();
+    let count = file.matches.len().min(file.replacements.len());
+
+    // Compute the byte ranges of all of the lines in the file, including their
+    // terminators. If the file ends with a newline (or is empty), we add a
+    // trailing empty line that only exists so that matches at the very end of
+    // the file still belong to a line.
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        lines.push(offset..offset + line.len());
+        offset += line.len();
+    }
+
+    let real_lines = lines.len();
+    if contents.is_empty() || contents.ends_with('\n') {
+        lines.push(contents.len()..contents.len());
+    }
+
+    let line_of = |pos: usize| lines.partition_point(|line| line.end <= pos).min(lines.len() - 1);
+
+    // Group all of the matches into changes over contiguous lines.
+    let mut changes: Vec<Change> = Vec::new();
+    for (index, m) in file.matches[..count].iter().enumerate() {
+        if file.replacements[index] == contents[m.start..m.end] {
+            continue;
+        }
+
+        let first = line_of(m.start);
+        let last = line_of(m.end.saturating_sub(1).max(m.start));
+
+        match changes.last_mut() {
+            Some(change) if first <= change.lines.end => {
+                change.lines.end = change.lines.end.max(last + 1);
+                change.matches.end = index + 1;
+            }
+            _ => changes.push(Change { lines: first..last + 1, matches: index..index + 1 }),
+        }
+    }
+
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = String::new();
+    let _ = writeln!(diff, "--- {old_path}");
+    let _ = writeln!(diff, "+++ {new_path}");
+
+    // The difference in the number of lines between the old and the new file
+    // that has accumulated over the previous hunks.
+    let mut delta: isize = 0;
+
+    for hunk in changes.chunk_by(|a, b| b.lines.start - a.lines.end <= 2 * CONTEXT_LINES) {
+        let start = hunk[0].lines.start.saturating_sub(CONTEXT_LINES);
+        let end = (hunk[hunk.len() - 1].lines.end + CONTEXT_LINES).min(real_lines);
+
+        let mut body = String::new();
+        let (mut old_count, mut new_count) = (0, 0);
+        let mut line = start;
+
+        for change in hunk {
+            for context in line..change.lines.start {
+                push_line(&mut body, ' ', &contents[lines[context].clone()]);
+            }
+
+            let range = lines[change.lines.start].start..lines[change.lines.end - 1].end;
+            let old = &contents[range.clone()];
+            let new = apply_in(
+                old,
+                range.start,
+                &file.matches[change.matches.clone()],
+                &file.replacements[change.matches.clone()],
+            );
+
+            for removed in old.split_inclusive('\n') {
+                push_line(&mut body, '-', removed);
+                old_count += 1;
+            }
+
+            for added in new.split_inclusive('\n') {
+                push_line(&mut body, '+', added);
+                new_count += 1;
+            }
+
+            old_count += change.lines.start - line;
+            new_count += change.lines.start - line;
+            line = change.lines.end;
+        }
+
+        for context in line..end.max(line) {
+            push_line(&mut body, ' ', &contents[lines[context].clone()]);
+            old_count += 1;
+            new_count += 1;
+        }
+
+        // By convention, an empty range refers to the line just before it.
+        let old_start = if old_count == 0 { start } else { start + 1 };
+        let new_start = (start as isize + delta) as usize + usize::from(new_count != 0);
+        delta += new_count as isize - old_count as isize;
+
+        let _ = writeln!(diff, "@@ -{old_start},{old_count} +{new_start},{new_count} @@");
+        diff.push_str(&body);
+    }
+
+    diff
+}
+
+/// Push a single line of a diff with the given `marker`, noting when the line
+/// is not terminated by a newline.
+fn push_line(diff: &mut String, marker: char, line: &str) {
+    diff.push(marker);
+    diff.push_str(line);
+
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn file(contents: &str, edits: &[(&str, &str)]) -> FileMatches {
+        let mut matches = vec![];
+        let mut replacements = vec![];
+
+        for (needle, replacement) in edits {
+            let start = contents.find(needle).unwrap();
+            matches.push(Match::new(start, start + needle.len()));
+            replacements.push(replacement.to_string());
+        }
+
+        FileMatches {
+            path: PathBuf::from("a.rs"),
+            contents: contents.into(),
+            matches,
+            replacements,
+            ..FileMatches::default()
+        }
+    }
+
+    #[test]
+    fn test_apply() {
+        let file = file("let foo = bar(foo);\n", &[("foo", "baz"), ("bar", "qux")]);
+        assert_eq!(apply(&file), "let baz = qux(foo);\n");
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let contents = "1\n2\n3\n4\nfoo\n6\n7\n8\n9\n10\n11\n12\nbar\n";
+        let file = file(contents, &[("foo", "FOO"), ("bar", "BAR")]);
+
+        let expected = "\
+--- a/a.rs
++++ b/a.rs
+@@ -2,7 +2,7 @@
+ 2
+ 3
+ 4
+-foo
++FOO
+ 6
+ 7
+ 8
+@@ -10,4 +10,4 @@
+ 10
+ 11
+ 12
+-bar
++BAR
+";
+        assert_eq!(unified_diff(&file, "a/a.rs", "b/a.rs"), expected);
+    }
+
+    #[test]
+    fn test_unified_diff_line_count_changes() {
+        let file = file("a\nfoo\nb", &[("foo", "x\ny"), ("b", "c")]);
+
+        let expected = "\
+--- a
++++ b
+@@ -1,3 +1,4 @@
+ a
+-foo
+-b
+\\ No newline at end of file
++x
++y
++c
+\\ No newline at end of file
+";
+        assert_eq!(unified_diff(&file, "a", "b"), expected);
+    }
+}
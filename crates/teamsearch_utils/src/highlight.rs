@@ -1,7 +1,67 @@
 //! Highlighting utilities.
-use std::ops::BitOr;
+use std::{
+    env,
+    ops::BitOr,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::ValueEnum;
+
+/// Whether highlighting is enabled, when it isn't, [highlight] leaves the
+/// text as is.
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable all highlighting.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check whether highlighting is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// When the output should be highlighted.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColourChoice {
+    /// Highlight the output if it is written to a terminal, unless the
+    /// `NO_COLOR` environment variable is set. Setting `CLICOLOR_FORCE`
+    /// forces the output to be highlighted regardless.
+    #[default]
+    Auto,
+
+    /// Always highlight the output.
+    Always,
+
+    /// Never highlight the output.
+    Never,
+}
+
+impl ColourChoice {
+    /// Decide whether the output that is written to a stream should be
+    /// highlighted, where `is_terminal` is whether the stream is a terminal.
+    pub fn should_colour(self, is_terminal: bool) -> bool {
+        let var = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+
+        match self {
+            ColourChoice::Always => true,
+            ColourChoice::Never => false,
+            ColourChoice::Auto => {
+                if var("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+                    true
+                } else if var("NO_COLOR").is_some() || var("TERM").is_some_and(|t| t == "dumb") {
+                    false
+                } else {
+                    is_terminal
+                }
+            }
+        }
+    }
+}
 
 /// Variants of highlighter colour that can be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Colour {
     Black,
     Red,
@@ -13,14 +73,64 @@ pub enum Colour {
     White,
 }
 
+impl Colour {
+    /// The escape code that applies this colour to the background of text.
+    pub fn background_code(&self) -> String {
+        let code = match self {
+            Colour::Black => 40,
+            Colour::Red => 41,
+            Colour::Green => 42,
+            Colour::Yellow => 43,
+            Colour::Blue => 44,
+            Colour::Magenta => 45,
+            Colour::Cyan => 46,
+            Colour::White => 47,
+        };
+
+        format!("\u{001b}[{code}m")
+    }
+}
+
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "black" => Ok(Colour::Black),
+            "red" => Ok(Colour::Red),
+            "green" => Ok(Colour::Green),
+            "yellow" => Ok(Colour::Yellow),
+            "blue" => Ok(Colour::Blue),
+            "magenta" => Ok(Colour::Magenta),
+            "cyan" => Ok(Colour::Cyan),
+            "white" => Ok(Colour::White),
+            _ => Err(format!("unrecognized colour `{s}`")),
+        }
+    }
+}
+
 /// Colour modifiers specifying if the colour should also
 /// apply a text effect such as bold or underlined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Modifier {
     Bold,
     Underline,
     Inverted,
 }
 
+impl FromStr for Modifier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bold" => Ok(Modifier::Bold),
+            "underline" => Ok(Modifier::Underline),
+            "inverted" => Ok(Modifier::Inverted),
+            _ => Err(format!("unrecognized style `{s}`")),
+        }
+    }
+}
+
 /// Trait that enables combining colour and modifiers using the [BitOr]
 /// operator.
 impl BitOr<Modifier> for Colour {
@@ -47,6 +157,14 @@ pub trait Highlighter {
     fn escape_code(&self) -> String;
 }
 
+/// Highlighter trait implementation for references to a highlighter, so that
+/// highlighters can be re-used without cloning them.
+impl<H: Highlighter> Highlighter for &H {
+    fn escape_code(&self) -> String {
+        (*self).escape_code()
+    }
+}
+
 /// Highlighter trait implementation for a [Colour].
 impl Highlighter for Colour {
     fn escape_code(&self) -> String {
@@ -93,16 +211,57 @@ impl Highlighter for Decoration {
     }
 }
 
+/// A style that is built up at runtime, i.e. from the configuration of the
+/// user, with an optional foreground and background colour, and any number of
+/// modifiers. A style without any of these leaves the text as is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+    /// The colour of the text.
+    pub foreground: Option<Colour>,
+    /// The colour of the background of the text.
+    pub background: Option<Colour>,
+    /// The text modifiers to apply.
+    pub modifiers: Vec<Modifier>,
+}
+
+impl From<Colour> for Style {
+    fn from(colour: Colour) -> Self {
+        Style { foreground: Some(colour), ..Style::default() }
+    }
+}
+
+/// Highlighter trait implementation for a [Style].
+impl Highlighter for Style {
+    fn escape_code(&self) -> String {
+        let foreground = self.foreground.iter().map(Highlighter::escape_code);
+        let background = self.background.iter().map(Colour::background_code);
+        let modifiers = self.modifiers.iter().map(Highlighter::escape_code);
+
+        foreground.chain(background).chain(modifiers).collect()
+    }
+}
+
 /// General function to apply a highlighter on a string. This will call the
 /// provided [Highlighter] implementation and then apply it to the passed
-/// message, resetting the effect at the end of the message.
+/// message, resetting the effect at the end of the message. If highlighting
+/// is disabled, the message is left as is.
 pub fn highlight(highlighter: impl Highlighter, message: impl ToString) -> String {
+    highlight_if(is_enabled(), highlighter, message)
+}
+
+/// Like [highlight], but only applies the highlighter if `enabled`, for
+/// streams whose highlighting is decided separately from the output.
+pub fn highlight_if(
+    enabled: bool,
+    highlighter: impl Highlighter,
+    message: impl ToString,
+) -> String {
     const RESET: &str = "\u{001b}[0m";
 
-    highlighter
-        .escape_code()
-        .chars()
-        .chain(message.to_string().chars())
-        .chain(RESET.chars())
-        .collect()
+    let code = highlighter.escape_code();
+    if !enabled || code.is_empty() {
+        return message.to_string();
+    }
+
+    code.chars().chain(message.to_string().chars()).chain(RESET.chars()).collect()
 }
@@ -19,3 +19,61 @@ pub fn get_line_range(contents: &str, byte_pos: usize) -> (usize, usize, usize)
 
     (line_num, line_start, line_end)
 }
+
+/// An index of the line boundaries within some text, used to look up the line
+/// of many byte positions without re-scanning the text for each of them.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    /// The byte offset of the start of each line.
+    starts: Vec<usize>,
+
+    /// The total length of the text.
+    len: usize,
+}
+
+impl LineIndex {
+    /// Create a new [LineIndex] for the given `contents`.
+    pub fn new(contents: &str) -> Self {
+        LineIndex::from_bytes(contents.as_bytes())
+    }
+
+    /// Create a new [LineIndex] for the given `contents`, which don't have to
+    /// be valid UTF-8.
+    pub fn from_bytes(contents: &[u8]) -> Self {
+        let starts = std::iter::once(0)
+            .chain(contents.iter().enumerate().filter(|&(_, b)| *b == b'\n').map(|(i, _)| i + 1))
+            .collect();
+
+        LineIndex { starts, len: contents.len() }
+    }
+
+    /// Get the line number (1-indexed) and byte range for a given byte position,
+    /// equivalent to [get_line_range].
+    pub fn line_range(&self, byte_pos: usize) -> (usize, usize, usize) {
+        let line = self.starts.partition_point(|&start| start <= byte_pos);
+        let line_start = self.starts[line - 1];
+        let line_end = self.starts.get(line).map_or(self.len, |next| next - 1);
+
+        (line, line_start, line_end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index_matches_line_range() {
+        for contents in ["", "foo", "foo\nbar", "foo\n\nbar\n", "\r\nbaz\r\n"] {
+            let index = LineIndex::new(contents);
+
+            for pos in 0..=contents.len() {
+                assert_eq!(
+                    index.line_range(pos),
+                    get_line_range(contents, pos),
+                    "{contents:?}@{pos}"
+                );
+            }
+        }
+    }
+}
@@ -1,24 +1,28 @@
 //! Logging utilities. This defines a simple logger with a
 //! style which should be used across the compiler to log and print messages.
 
-use std::{fmt, io::Write};
+use std::{collections::BTreeMap, fmt, io::Write};
 
-use ::log::{Level, Log, Metadata, Record};
+use ::log::{
+    Level, Log, Metadata, Record,
+    kv::{self, Key, Value, VisitSource},
+};
 use clap::ValueEnum;
 use once_cell::sync::OnceCell;
+use serde::Serialize;
 
 use crate::{
-    highlight::{Colour, Modifier, highlight},
+    highlight::{Colour, ColourChoice, Modifier, highlight_if},
     stream::CompilerOutputStream,
     stream_writeln,
 };
 
-/// The [CompilerMessagingFormat] specifies the message mode that the compiler
-/// will use to to emit and receive messages.
+/// The [MessagingFormat] specifies the message mode that the tool will use
+/// to emit messages.
 #[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum MessagingFormat {
-    /// All messages that are emitted to and from the compiler will be in JSON
-    /// format according to the schema that represents [CompilerMessage].
+    /// All messages are emitted to `stderr` as JSON objects, one per line,
+    /// according to the schema of [JsonMessage].
     Json,
 
     /// Normal mode is the classic emission of messages as the compiler would
@@ -49,6 +53,10 @@ pub struct ToolLogger {
 
     /// The format to use when logging information.
     messaging_format: OnceCell<MessagingFormat>,
+
+    /// Whether the messages that are written to the output and error streams
+    /// are highlighted, which is decided separately for each of the streams.
+    colours: OnceCell<(bool, bool)>,
 }
 
 impl ToolLogger {
@@ -58,14 +66,34 @@ impl ToolLogger {
             output_stream: OnceCell::new(),
             error_stream: OnceCell::new(),
             messaging_format: OnceCell::new(),
+            colours: OnceCell::new(),
         }
     }
 
+    /// Decide whether the messages of each of the streams are highlighted,
+    /// which must be done after the streams are set.
+    pub fn set_colour_choice(&self, choice: ColourChoice) {
+        let output = self
+            .output_stream
+            .get()
+            .is_some_and(|stream| choice.should_colour(stream.is_terminal()));
+        let error = self
+            .error_stream
+            .get()
+            .is_some_and(|stream| choice.should_colour(stream.is_terminal()));
+        self.colours.set((output, error)).unwrap();
+    }
+
     /// Set the [CompilerLogger] messaging format.
     pub fn set_messaging_format(&self, format: MessagingFormat) {
         self.messaging_format.set(format).unwrap();
     }
 
+    /// Get the [MessagingFormat] of the logger.
+    pub fn messaging_format(&self) -> MessagingFormat {
+        self.messaging_format.get().copied().unwrap_or_default()
+    }
+
     fn log_default(&self, out: &mut dyn Write, record: &Record, level_prefix: String) {
         stream_writeln!(
             out,
@@ -74,6 +102,45 @@ impl ToolLogger {
             message = record.args()
         );
     }
+
+    fn log_json(&self, out: &mut dyn Write, record: &Record) {
+        let mut fields = FieldVisitor::default();
+        let _ = record.key_values().visit(&mut fields);
+
+        let message = JsonMessage {
+            level: record.level().as_str().to_lowercase(),
+            message: record.args().to_string(),
+            fields: fields.0,
+        };
+
+        stream_writeln!(out, "{}", serde_json::to_string(&message).unwrap());
+    }
+}
+
+/// A log record, as it is emitted with the [MessagingFormat::Json] format.
+#[derive(Serialize)]
+pub struct JsonMessage {
+    /// The level of the record, i.e. `error`, `warn`, `info`, `debug` or
+    /// `trace`.
+    pub level: String,
+
+    /// The formatted message of the record.
+    pub message: String,
+
+    /// The key/value pairs that are attached to the record.
+    pub fields: BTreeMap<String, serde_json::Value>,
+}
+
+/// Collects the key/value pairs of a record.
+#[derive(Default)]
+struct FieldVisitor(BTreeMap<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for FieldVisitor {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = serde_json::to_value(&value).unwrap_or_else(|_| value.to_string().into());
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
 }
 
 impl Log for ToolLogger {
@@ -86,19 +153,27 @@ impl Log for ToolLogger {
             return;
         }
 
-        // Custom colour formatting for the log level
-        let level_prefix = match record.level() {
-            Level::Error => highlight(Colour::Red | Modifier::Bold, "error"),
-            Level::Warn => highlight(Colour::Yellow | Modifier::Bold, "warn"),
-            Level::Info => highlight(Colour::Blue | Modifier::Bold, "info"),
-            Level::Debug => highlight(Colour::Blue | Modifier::Bold, "debug"),
-            Level::Trace => highlight(Colour::Magenta | Modifier::Bold, "trace"),
-        };
+        // Structured messages are always written to `stderr`, so that they
+        // don't get mixed up with the output of the tool.
+        if self.messaging_format() == MessagingFormat::Json {
+            self.log_json(&mut self.error_stream.get().unwrap().clone(), record);
+            return;
+        }
 
-        let mut out = if record.level() == Level::Error {
-            self.error_stream.get().unwrap().clone()
+        let (output_colour, error_colour) = self.colours.get().copied().unwrap_or_default();
+        let (mut out, colour) = if record.level() == Level::Error {
+            (self.error_stream.get().unwrap().clone(), error_colour)
         } else {
-            self.output_stream.get().unwrap().clone()
+            (self.output_stream.get().unwrap().clone(), output_colour)
+        };
+
+        // Custom colour formatting for the log level
+        let level_prefix = match record.level() {
+            Level::Error => highlight_if(colour, Colour::Red | Modifier::Bold, "error"),
+            Level::Warn => highlight_if(colour, Colour::Yellow | Modifier::Bold, "warn"),
+            Level::Info => highlight_if(colour, Colour::Blue | Modifier::Bold, "info"),
+            Level::Debug => highlight_if(colour, Colour::Blue | Modifier::Bold, "debug"),
+            Level::Trace => highlight_if(colour, Colour::Magenta | Modifier::Bold, "trace"),
         };
 
         self.log_default(&mut out, record, level_prefix);
@@ -107,16 +182,6 @@ impl Log for ToolLogger {
     fn flush(&self) {}
 }
 
-// #[derive(Default)]
-// struct LoggerVisitor<'l>(BTreeMap<Key<'l>, Value<'l>>);
-
-// impl<'l> Visitor<'l> for LoggerVisitor<'l> {
-//     fn visit_pair(&mut self, key: Key<'l>, value: Value<'l>) -> Result<(),
-// Error> {         self.0.insert(key, value);
-//         Ok(())
-//     }
-// }
-
 /// This is used a wrapper around [`writeln!`] which integrates writing
 /// to a specified stream.
 ///
@@ -164,3 +229,55 @@ macro_rules! stream_less_ewriteln {
         eprintln!($($arg)*);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use ::log::kv::ToValue;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_json_messages() {
+        let (output, error) = (CompilerOutputStream::owned(), CompilerOutputStream::owned());
+        let logger = ToolLogger::new();
+        logger.output_stream.set(output.clone()).unwrap();
+        logger.error_stream.set(error.clone()).unwrap();
+        logger.set_messaging_format(MessagingFormat::Json);
+
+        let owners = vec!["@org/core"];
+        let fields = [
+            ("path", "src/a.rs".to_value()),
+            ("matches", 3.to_value()),
+            ("owners", Value::from_serde(&owners)),
+        ];
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("found {} matches", 3))
+                .key_values(&fields)
+                .build(),
+        );
+        logger.log(&Record::builder().level(Level::Warn).args(format_args!("careful")).build());
+
+        // Every record is a single line on `stderr`, even the ones that would
+        // otherwise go to `stdout`.
+        let CompilerOutputStream::Owned(output) = output else { unreachable!() };
+        assert!(output.lock().unwrap().is_empty());
+
+        let CompilerOutputStream::Owned(error) = error else { unreachable!() };
+        let error = String::from_utf8(error.lock().unwrap().clone()).unwrap();
+        let messages = error.lines().map(serde_json::from_str::<serde_json::Value>);
+        assert_eq!(
+            messages.collect::<Result<Vec<_>, _>>().unwrap(),
+            [
+                json!({
+                    "level": "info",
+                    "message": "found 3 matches",
+                    "fields": {"path": "src/a.rs", "matches": 3, "owners": ["@org/core"]},
+                }),
+                json!({"level": "warn", "message": "careful", "fields": {}}),
+            ]
+        );
+    }
+}
@@ -8,7 +8,10 @@
 //! - It might be potentially used in the future with the meta-program to send
 //!   and receive messages from the compiler.
 
-use std::sync::{Arc, Mutex};
+use std::{
+    io::IsTerminal,
+    sync::{Arc, Mutex},
+};
 
 /// A [CompilerOutputStream] is used to specify where the output of the compiler
 /// should be written to. This is used by the [CompilerInterface] to provide
@@ -42,6 +45,15 @@ impl CompilerOutputStream {
     pub fn owned() -> Self {
         CompilerOutputStream::Owned(Arc::new(Mutex::new(Vec::new())))
     }
+
+    /// Check whether the stream is a terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            CompilerOutputStream::Stdout(stream) => stream.is_terminal(),
+            CompilerOutputStream::Stderr(stream) => stream.is_terminal(),
+            CompilerOutputStream::Owned(_) => false,
+        }
+    }
 }
 
 impl Clone for CompilerOutputStream {
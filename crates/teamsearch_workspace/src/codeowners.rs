@@ -4,12 +4,15 @@
 use std::{
     collections::HashMap,
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
 
 use anyhow::Result;
 use derive_more::Constructor;
 
-use crate::settings::{FilePattern, FilePatternSet};
+use teamsearch_utils::fs;
+
+use crate::settings::{FilePattern, FilePatternSet, Settings};
 
 #[derive(Debug, Constructor, Default)]
 pub struct CodeOwners {
@@ -19,11 +22,22 @@ pub struct CodeOwners {
     /// A pre-computed matcher for the owner.
     owner_set: FilePatternSet,
 
+    /// A pre-computed matcher for each of the teams, in the order in which
+    /// the teams first appear in the CODEOWNERS file.
+    team_sets: Vec<(String, FilePatternSet)>,
+
+    /// The rules that assign each of the teams, as they're written in the
+    /// CODEOWNERS file, in the same order as the patterns of the team.
+    rules: HashMap<String, Vec<String>>,
+
     /// The root directory of the repository.
     root: PathBuf,
 
     /// Generally ignored paths.
     pub ignored_patterns: Vec<FilePattern>,
+
+    /// A pre-computed matcher for the ignored paths.
+    ignored_set: FilePatternSet,
 }
 
 impl CodeOwners {
@@ -33,13 +47,15 @@ impl CodeOwners {
     }
 
     /// Get a [FilePatternSet] for a given team.
-    fn get_pattern_for_team(&self, team: &str) -> FilePatternSet {
-        FilePatternSet::try_from_iter(self.get_patterns_for_team(team).to_vec()).unwrap()
+    fn get_pattern_for_team(&self, team: &str) -> Option<&FilePatternSet> {
+        self.team_sets.iter().find(|(name, _)| name == team).map(|(_, set)| set)
     }
 
     /// Check if a file is owned by a team.
     pub fn is_owned_by(&self, path: &Path, team: &str) -> bool {
-        let set = self.get_pattern_for_team(team);
+        let Some(set) = self.get_pattern_for_team(team) else {
+            return false;
+        };
 
         // Get path relative to root if possible
         let relative_path = self.get_relative_path(path);
@@ -55,28 +71,33 @@ impl CodeOwners {
         self.owner_set.is_match(&path_pat)
     }
 
-    /// Lookup a file path to see which team owns it.
-    ///
-    /// @@Future: we need to expand this so it supports multiple teams.
-    /// The order should be based on the order of the teams in the CODEOWNERS
-    /// file.
+    /// Lookup a file path to see which teams own it. The teams are ordered
+    /// by their first appearance in the CODEOWNERS file.
     pub fn lookup(&self, path: &Path) -> Vec<String> {
         let path = self.get_relative_path(path);
         let path_pat = self.format_path_for_matching(&path);
 
-        let mut owners = vec![];
-
-        for owner in self.owners.keys() {
-            // @@Todo: we could potentially use a `OnceCell` here to cache the
-            // pattern set for each team.
-            let set = self.get_pattern_for_team(owner);
+        self.team_sets
+            .iter()
+            .filter(|(_, set)| set.is_match(&path_pat))
+            .map(|(owner, _)| owner.clone())
+            .collect()
+    }
 
-            if set.is_match(&path_pat) {
-                owners.push(owner.clone());
-            }
-        }
+    /// Lookup a file path to see which teams own it, along with the rule of the
+    /// CODEOWNERS file that assigns each of the teams. If multiple rules of a
+    /// team match, the last one takes precedence.
+    pub fn lookup_rules(&self, path: &Path) -> Vec<(String, String)> {
+        let path = self.get_relative_path(path);
+        let path_pat = self.format_path_for_matching(&path);
 
-        owners
+        self.team_sets
+            .iter()
+            .filter_map(|(owner, set)| {
+                let index = set.matches(&path_pat).into_iter().max()?;
+                Some((owner.clone(), self.rules[owner][index].clone()))
+            })
+            .collect()
     }
 
     /// Helper method to get a path relative to the root
@@ -104,6 +125,18 @@ impl CodeOwners {
         path_str
     }
 
+    /// Get all of the teams, in the order in which they first appear in the
+    /// CODEOWNERS file.
+    pub fn teams(&self) -> impl Iterator<Item = &str> {
+        self.team_sets.iter().map(|(team, _)| team.as_str())
+    }
+
+    /// Get the rules that assign a specific team, as they're written in the
+    /// CODEOWNERS file.
+    pub fn get_rules_for_team(&self, team: &str) -> &[String] {
+        self.rules.get(team).map_or(&[], |v| v)
+    }
+
     /// Get all patterns for a specific team.
     pub fn get_patterns_for_team(&self, team: &str) -> &[FilePattern] {
         self.owners.get(team).map_or(&[], |v| v)
@@ -114,12 +147,21 @@ impl CodeOwners {
         &self.ignored_patterns
     }
 
+    /// Check if a file matches any of the rules without owners.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let relative_path = self.get_relative_path(path);
+        let path_pat = self.format_path_for_matching(&relative_path);
+
+        self.ignored_set.is_match(&path_pat)
+    }
+
     /// Parse the contents of the CODEOWNERS file. This file format is very
     /// simple, the basics are as follows:
     ///
     /// - Comments are lines that start with `#`.
     ///
-    /// - Each line is a path, followed by a list of owners.
+    /// - Each line is a path, followed by a list of owners. Whitespace within
+    ///   the path is escaped with a backslash, see [escape_path].
     ///
     /// - If no owners are specified, we consider these to be owned by anyone.
     ///   For the purpose of this tool, we can openly "ignore" these paths,
@@ -144,7 +186,27 @@ impl CodeOwners {
             anyhow::bail!("Failed to read the CODEOWNERS file at {:?}", path);
         })?;
 
+        Self::parse(&contents, root)
+    }
+
+    /// Parse the CODEOWNERS file of the given [Settings], which is read from
+    /// the git revision of the settings if there is one, and otherwise from
+    /// the file system.
+    pub fn parse_from_settings(settings: &Settings, root: &Path) -> Result<Self> {
+        match &settings.revision {
+            Some(revision) => {
+                let path = fs::normalize_path(&settings.codeowners);
+                Self::parse(&revision.read_to_string(&path)?, root)
+            }
+            None => Self::parse_from_file(&settings.codeowners, root),
+        }
+    }
+
+    /// Parse the `contents` of a CODEOWNERS file, see [Self::parse_from_file]
+    /// for the details of the format.
+    pub fn parse(contents: &str, root: &Path) -> Result<Self> {
         let mut owners = CodeOwners { root: root.to_path_buf(), ..CodeOwners::default() };
+        let mut order = vec![];
 
         for line in contents.lines() {
             let line = line.trim();
@@ -154,9 +216,17 @@ impl CodeOwners {
                 continue;
             }
 
-            let mut parts = line.split_whitespace();
-            let path = parts.next().unwrap();
-            let owners_annotations: Vec<String> = parts.map(str::to_string).collect();
+            // The path ends at the first whitespace that isn't escaped, the
+            // escapes themselves are left for the glob to interpret.
+            let mut escaped = false;
+            let end = line.char_indices().find(|&(_, c)| {
+                let end = c.is_whitespace() && !escaped;
+                escaped = c == '\\' && !escaped;
+                end
+            });
+            let (path, rest) = line.split_at(end.map_or(line.len(), |(index, _)| index));
+            let owners_annotations: Vec<String> =
+                rest.split_whitespace().map(str::to_string).collect();
 
             let convert_to_user = |path: &str| {
                 let mut buf = path.to_owned();
@@ -182,19 +252,145 @@ impl CodeOwners {
 
             // Update all of the owners for the given path.
             for owner in owners_annotations {
+                if !owners.owners.contains_key(&owner) {
+                    order.push(owner.clone());
+                }
+
                 let abs = convert_to_user(path);
+                owners.rules.entry(owner.clone()).or_default().push(path.to_string());
                 owners.owners.entry(owner).or_default().push(FilePattern::User(abs));
             }
         }
 
-        // Now compute the matcher for the owners.
+        // Now compute the matcher for the owners, and for each of the teams.
+        // The teams are kept in the order of the CODEOWNERS file, so that the
+        // owners of a file are always reported in the same order.
         owners.owner_set =
-            FilePatternSet::try_from_iter(owners.owners.values().flatten().cloned()).unwrap();
+            FilePatternSet::try_from_iter(owners.owners.values().flatten().cloned())?;
+        owners.ignored_set = FilePatternSet::try_from_iter(owners.ignored_patterns.clone())?;
+
+        owners.team_sets = order
+            .into_iter()
+            .map(|team| {
+                let set = FilePatternSet::try_from_iter(owners.owners[&team].clone())?;
+                Ok((team, set))
+            })
+            .collect::<Result<_>>()?;
 
         Ok(owners)
     }
 }
 
+/// The locations of the CODEOWNERS file within a repository, in the order in
+/// which they're looked for.
+pub const CODEOWNERS_LOCATIONS: &[&str] = &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+/// A parsed CODEOWNERS file, along with where it was read from.
+#[derive(Debug, Default)]
+pub struct CodeOwnersFile {
+    /// The path of the CODEOWNERS file.
+    pub path: PathBuf,
+
+    /// The parsed contents of the file.
+    pub codeowners: CodeOwners,
+}
+
+/// The CODEOWNERS files that govern a tree of files. The outermost file governs
+/// all of the files, except for the files within nested repositories (e.g. git
+/// submodules) that have a CODEOWNERS file of their own. The nested files are
+/// discovered as the tree is walked.
+#[derive(Debug, Default)]
+pub struct CodeOwnersTree {
+    /// The outermost CODEOWNERS file.
+    outer: Arc<CodeOwnersFile>,
+
+    /// The CODEOWNERS files of the nested repositories, keyed by the root of
+    /// each of the repositories.
+    nested: RwLock<HashMap<PathBuf, Arc<CodeOwnersFile>>>,
+}
+
+impl CodeOwnersTree {
+    pub fn new(path: PathBuf, codeowners: CodeOwners) -> Self {
+        let outer = Arc::new(CodeOwnersFile { path, codeowners });
+        CodeOwnersTree { outer, nested: RwLock::default() }
+    }
+
+    /// Get the outermost CODEOWNERS file.
+    pub fn outer(&self) -> &CodeOwners {
+        &self.outer.codeowners
+    }
+
+    /// Check whether the team exists in any of the CODEOWNERS files that have
+    /// been found so far.
+    pub fn has_team(&self, team: &str) -> bool {
+        self.outer.codeowners.has_team(team)
+            || self.nested.read().unwrap().values().any(|file| file.codeowners.has_team(team))
+    }
+
+    /// Check whether `dir` is the root of a nested repository with its own
+    /// CODEOWNERS file, in which case that file governs all of the files
+    /// within `dir`.
+    pub fn discover(&self, dir: &Path) -> Result<()> {
+        if dir == self.outer.codeowners.root
+            || !dir.join(".git").exists()
+            || self.nested.read().unwrap().contains_key(dir)
+        {
+            return Ok(());
+        }
+
+        let Some(path) =
+            CODEOWNERS_LOCATIONS.iter().map(|location| dir.join(location)).find(|p| p.is_file())
+        else {
+            return Ok(());
+        };
+
+        let codeowners = CodeOwners::parse_from_file(&path, dir)?;
+        let file = Arc::new(CodeOwnersFile { path, codeowners });
+        self.nested.write().unwrap().insert(dir.to_path_buf(), file);
+        Ok(())
+    }
+
+    /// Discover the nested repositories that contain `path`, which is needed
+    /// for the paths that aren't found by walking the tree. If `path` is a
+    /// directory, it might be the root of a nested repository itself.
+    pub fn discover_ancestors(&self, path: &Path) -> Result<()> {
+        let root = &self.outer.codeowners.root;
+        let dirs = path.ancestors().skip(if path.is_dir() { 0 } else { 1 });
+
+        for dir in dirs.take_while(|dir| dir.starts_with(root) && dir != root) {
+            self.discover(dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the CODEOWNERS file that governs `path`, which is the one of the
+    /// closest repository that contains it.
+    pub fn resolve(&self, path: &Path) -> Arc<CodeOwnersFile> {
+        let nested = self.nested.read().unwrap();
+        nested
+            .iter()
+            .filter(|(root, _)| path.starts_with(root))
+            .max_by_key(|(root, _)| root.components().count())
+            .map_or_else(|| self.outer.clone(), |(_, file)| file.clone())
+    }
+}
+
+/// Escape a relative `path` so that it can be used as the pattern of a rule
+/// in a CODEOWNERS file, where whitespace would otherwise end the pattern and
+/// the glob characters would match other files as well.
+pub fn escape_path(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_whitespace() || matches!(c, '\\' | '*' | '?' | '[' | ']' | '{' | '}') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -206,6 +402,7 @@ mod tests {
     use tempfile::{TempDir, tempdir};
 
     use super::*;
+    use crate::{git::Revision, testing::repository};
 
     /// Helper function to create a temporary directory with a CODEOWNERS file
     fn setup_test_dir(codeowners_content: &str) -> (TempDir, PathBuf) {
@@ -327,10 +524,9 @@ mod tests {
         assert_eq!(src_owners.len(), 1);
         assert!(src_owners.contains(&"@dev-team".to_string()));
 
+        // The owners are ordered by their first appearance in the file.
         let docs_owners = code_owners.lookup(&root.join("docs/README.md"));
-        assert_eq!(docs_owners.len(), 2);
-        assert!(docs_owners.contains(&"@docs-team".to_string()));
-        assert!(docs_owners.contains(&"@dev-team".to_string()));
+        assert_eq!(docs_owners, ["@dev-team", "@docs-team"]);
 
         // Test lookup for directories
         let src_dir_owners = code_owners.lookup(&root.join("src/"));
@@ -338,6 +534,34 @@ mod tests {
         assert!(src_dir_owners.contains(&"@dev-team".to_string()));
     }
 
+    #[test]
+    fn test_lookup_rules() {
+        let codeowners_content = r#"
+/src/ @dev-team
+/src/*.rs @docs-team @dev-team
+/docs/ @docs-team
+        "#;
+
+        let (temp_dir, codeowners_path) = setup_test_dir(codeowners_content);
+        let root = temp_dir.path().to_path_buf();
+
+        let code_owners = CodeOwners::parse_from_file(&codeowners_path, &root).unwrap();
+
+        // The last matching rule of each team is reported.
+        assert_eq!(
+            code_owners.lookup_rules(&root.join("src/main.rs")),
+            vec![
+                ("@dev-team".to_string(), "/src/*.rs".to_string()),
+                ("@docs-team".to_string(), "/src/*.rs".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            code_owners.lookup_rules(&root.join("docs/README.md")),
+            vec![("@docs-team".to_string(), "/docs/".to_string())]
+        );
+    }
+
     #[test]
     fn test_complex_patterns() {
         let codeowners_content = r#"
@@ -406,4 +630,58 @@ mod tests {
         assert!(lib_owners.contains(&"@sec-team".to_string()));
         assert!(lib_owners.contains(&"@dev-team".to_string()));
     }
+
+    #[test]
+    fn test_nested_codeowners() {
+        let (temp_dir, codeowners_path) = setup_test_dir("/src/ @dev-team\n/vendor/ @vendor-team");
+        let root = temp_dir.path().to_path_buf();
+
+        let nested = root.join("vendor/lib");
+        fs::create_dir_all(nested.join(".git")).unwrap();
+        fs::create_dir_all(nested.join(".github")).unwrap();
+        fs::write(nested.join(".github/CODEOWNERS"), "/src/ @lib-team").unwrap();
+
+        let codeowners = CodeOwners::parse_from_file(&codeowners_path, &root).unwrap();
+        let tree = CodeOwnersTree::new(codeowners_path.clone(), codeowners);
+        tree.discover_ancestors(&nested.join("src/lib.rs")).unwrap();
+
+        let file = tree.resolve(&nested.join("src/lib.rs"));
+        assert_eq!(file.path, nested.join(".github/CODEOWNERS"));
+        assert_eq!(file.codeowners.lookup(&nested.join("src/lib.rs")), ["@lib-team"]);
+
+        // The outer file still governs everything outside of the nested
+        // repository.
+        let file = tree.resolve(&root.join("src/main.rs"));
+        assert_eq!(file.path, codeowners_path);
+        assert_eq!(file.codeowners.lookup(&root.join("vendor/other.rs")), ["@vendor-team"]);
+    }
+
+    #[test]
+    fn test_parse_from_settings() {
+        let repo = repository(&[("CODEOWNERS", "/src/ @org/old\n")]);
+        let root = repo.path();
+        fs::write(root.join("CODEOWNERS"), "/src/ @org/new\n").unwrap();
+
+        let mut settings = Settings::new(true, root.join("CODEOWNERS"));
+        let lookup = |settings: &Settings| {
+            CodeOwners::parse_from_settings(settings, root).unwrap().lookup(&root.join("src/a.rs"))
+        };
+        assert_eq!(lookup(&settings), ["@org/new"]);
+
+        settings.revision = Some(Revision::new(root, "HEAD").unwrap());
+        assert_eq!(lookup(&settings), ["@org/old"]);
+    }
+
+    #[test]
+    fn test_escaped_paths() {
+        let path = "dir with space/[id].rs";
+        let contents = format!("/{} @org/web\n/other/ @org/api\n", escape_path(path));
+        assert_eq!(escape_path(path), r"dir\ with\ space/\[id\].rs");
+
+        let root = Path::new("/repo");
+        let code_owners = CodeOwners::parse(&contents, root).unwrap();
+        assert_eq!(code_owners.lookup(&root.join(path)), ["@org/web"]);
+        assert!(code_owners.lookup(&root.join("dir with space/i.rs")).is_empty());
+        assert_eq!(code_owners.lookup(&root.join("other/a.rs")), ["@org/api"]);
+    }
 }
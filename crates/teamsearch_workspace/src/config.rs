@@ -0,0 +1,395 @@
+//! Implementation of the layered `teamsearch.toml` configuration. The
+//! configuration is read from a user-level file and from the file of the
+//! project, where the values of the project take precedence over those of the
+//! user, and the command line takes precedence over both:
+//!
+//! ```toml
+//! codeowners = ".github/CODEOWNERS"
+//! exclude = ["vendor/**", "third_party/**"]
+//! format = "json"
+//! color = "never"
+//! threads = 4
+//! registry = "teams.yaml"
+//!
+//! [aliases]
+//! web = "@org/web"
+//! frontend = ["web", "@org/mobile", "@org/design-system"]
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use itertools::Itertools;
+use serde::Deserialize;
+
+/// The name of the configuration file.
+pub const CONFIG_FILE_NAME: &str = "teamsearch.toml";
+
+/// The contents of a single configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// The path of the CODEOWNERS file, relative to the configuration file.
+    pub codeowners: Option<PathBuf>,
+
+    /// Paths that should be excluded from the search.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// The format in which the results are printed.
+    pub format: Option<String>,
+
+    /// When to highlight the output with colours.
+    pub color: Option<String>,
+
+    /// The number of threads that are used to walk and search the files.
+    pub threads: Option<usize>,
+
+    /// The path of the team registry, relative to the configuration file.
+    pub registry: Option<PathBuf>,
+
+    /// Short names for teams or groups of teams, which can be used wherever
+    /// a team is accepted.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Alias>,
+}
+
+/// The teams that an alias stands for, where each of the teams may itself be
+/// another alias.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Alias {
+    /// A short name for a single team.
+    Team(String),
+
+    /// A named group of teams.
+    Group(Vec<String>),
+}
+
+impl ConfigFile {
+    /// Read and parse the configuration file at the given path.
+    pub fn parse_from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("Failed to read the configuration at {path:?}: {err}."))?;
+        Self::parse(&contents)
+            .map_err(|err| anyhow!("Invalid configuration at {path:?}: {}", err.to_string().trim()))
+    }
+
+    /// Parse the `contents` of a configuration file.
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Source {
+    /// The value wasn't set anywhere, and so the default is used.
+    #[default]
+    Default,
+
+    /// The user-level configuration file at the given path.
+    User(PathBuf),
+
+    /// The configuration file of the project at the given path.
+    Project(PathBuf),
+
+    /// The command line.
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::User(path) => write!(f, "user config {}", path.display()),
+            Source::Project(path) => write!(f, "project config {}", path.display()),
+            Source::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// A configuration value, along with where it came from.
+#[derive(Debug, Clone)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Sourced<T> {
+    pub fn new(value: T, source: Source) -> Self {
+        Sourced { value, source }
+    }
+}
+
+/// The configuration that results from merging all of the layers.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// The path of the CODEOWNERS file.
+    pub codeowners: Option<Sourced<PathBuf>>,
+
+    /// Paths that should be excluded from the search. The exclusions of all
+    /// of the layers are combined, rather than overridden.
+    pub exclude: Vec<Sourced<String>>,
+
+    /// The format in which the results are printed.
+    pub format: Option<Sourced<String>>,
+
+    /// When to highlight the output with colours.
+    pub color: Option<Sourced<String>>,
+
+    /// The number of threads that are used to walk and search the files.
+    pub threads: Option<Sourced<usize>>,
+
+    /// The path of the team registry.
+    pub registry: Option<Sourced<PathBuf>>,
+
+    /// The teams of each of the aliases, keyed by the name without a leading
+    /// `@`.
+    pub aliases: BTreeMap<String, Sourced<Vec<String>>>,
+}
+
+impl Config {
+    /// Load the user-level configuration, and then the configuration of the
+    /// project that contains `dir` on top of it.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let mut config = Config::default();
+
+        for (path, source) in [
+            user_config_path().map(|path| (path.clone(), Source::User(path))),
+            project_config_path(dir).map(|path| (path.clone(), Source::Project(path))),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_file() {
+                config.merge(ConfigFile::parse_from_file(&path)?, &path, source);
+            }
+        }
+
+        // Check that none of the aliases are part of a cycle, so that any
+        // mistakes are reported even if the aliases aren't used.
+        for alias in config.aliases.keys() {
+            config.resolve_team(alias)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Merge the given configuration `file` at `path` on top of this one.
+    pub fn merge(&mut self, file: ConfigFile, path: &Path, source: Source) {
+        // The CODEOWNERS file and the registry are relative to the
+        // configuration file, rather than the directory that `teamsearch` is
+        // run from.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        if let Some(codeowners) = file.codeowners {
+            self.codeowners = Some(Sourced::new(dir.join(codeowners), source.clone()));
+        }
+
+        if let Some(registry) = file.registry {
+            self.registry = Some(Sourced::new(dir.join(registry), source.clone()));
+        }
+
+        let exclude = file.exclude.into_iter().map(|path| Sourced::new(path, source.clone()));
+        self.exclude.extend(exclude);
+
+        if let Some(format) = file.format {
+            self.format = Some(Sourced::new(format, source.clone()));
+        }
+
+        if let Some(color) = file.color {
+            self.color = Some(Sourced::new(color, source.clone()));
+        }
+
+        if let Some(threads) = file.threads {
+            self.threads = Some(Sourced::new(threads, source.clone()));
+        }
+
+        for (alias, teams) in file.aliases {
+            let alias = alias.trim_start_matches('@').to_string();
+            let teams = match teams {
+                Alias::Team(team) => vec![team],
+                Alias::Group(teams) => teams,
+            };
+
+            self.aliases.insert(alias, Sourced::new(teams, source.clone()));
+        }
+    }
+
+    /// Get the teams that the given `team` stands for, expanding any aliases
+    /// recursively. If `team` isn't an alias, it stands for itself. Only bare
+    /// names can be aliases, names that start with `@` are always teams, so
+    /// that an alias can stand for a team of the same name.
+    pub fn resolve_team(&self, team: &str) -> Result<Vec<String>> {
+        let mut teams = vec![];
+        self.expand_team(team, &mut vec![], &mut teams)?;
+        Ok(teams)
+    }
+
+    /// Expand the given `team` into `teams`, where `stack` holds the aliases
+    /// that are currently being expanded.
+    fn expand_team<'a>(
+        &'a self,
+        team: &'a str,
+        stack: &mut Vec<&'a str>,
+        teams: &mut Vec<String>,
+    ) -> Result<()> {
+        let alias = if team.starts_with('@') { None } else { self.aliases.get(team) };
+        let Some(alias) = alias else {
+            let team = if team.starts_with('@') { team.to_string() } else { format!("@{team}") };
+            if !teams.contains(&team) {
+                teams.push(team);
+            }
+
+            return Ok(());
+        };
+
+        if stack.contains(&team) {
+            let mut cycle = stack.iter().skip_while(|alias| **alias != team).chain([&team]);
+            return Err(anyhow!(
+                "The alias `{team}` in the {} refers to itself: {}.",
+                alias.source,
+                cycle.join(" -> ")
+            ));
+        }
+
+        stack.push(team);
+        for member in &alias.value {
+            self.expand_team(member, stack, teams)?;
+        }
+        stack.pop();
+
+        Ok(())
+    }
+}
+
+/// Find the configuration file of the project that contains `dir`, which is
+/// the closest one within the git repository of `dir`. If `dir` isn't within
+/// a repository, all of its ancestors are checked.
+pub fn project_config_path(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let path = dir.join(CONFIG_FILE_NAME);
+        if path.is_file() {
+            return Some(path);
+        }
+
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Get the path of the user-level configuration file, which lives in the
+/// configuration directory of the platform, i.e. `$XDG_CONFIG_HOME` or
+/// `~/.config` on Unix and `%APPDATA%` on Windows.
+pub fn user_config_path() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    let dir = if cfg!(windows) {
+        var("APPDATA").map(PathBuf::from)
+    } else {
+        var("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    dir.map(|dir| dir.join("teamsearch").join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_layers() {
+        let user = ConfigFile::parse(
+            r#"
+            codeowners = "CODEOWNERS"
+            exclude = ["vendor/**"]
+            format = "json"
+            threads = 2
+
+            [aliases]
+            web = "@org/web"
+            frontend = ["web", "org/mobile", "@org/web"]
+            "#,
+        )
+        .unwrap();
+
+        let project = ConfigFile::parse(
+            r#"
+            codeowners = ".github/CODEOWNERS"
+            exclude = ["third_party/**"]
+            threads = 8
+            "#,
+        )
+        .unwrap();
+
+        let (user_path, project_path) = (Path::new("/home/a/u.toml"), Path::new("/repo/p.toml"));
+        let mut config = Config::default();
+        config.merge(user, user_path, Source::User(user_path.into()));
+        config.merge(project, project_path, Source::Project(project_path.into()));
+
+        let codeowners = config.codeowners.as_ref().unwrap();
+        assert_eq!(codeowners.value, Path::new("/repo/.github/CODEOWNERS"));
+        assert_eq!(codeowners.source, Source::Project(project_path.into()));
+
+        assert_eq!(
+            config.exclude.iter().map(|e| e.value.as_str()).collect::<Vec<_>>(),
+            ["vendor/**", "third_party/**"]
+        );
+
+        assert_eq!(config.format.as_ref().unwrap().source, Source::User(user_path.into()));
+        assert_eq!(config.threads.as_ref().unwrap().value, 8);
+        assert_eq!(config.resolve_team("web").unwrap(), ["@org/web"]);
+        assert_eq!(config.resolve_team("@web").unwrap(), ["@web"]);
+        assert_eq!(config.resolve_team("@org/api").unwrap(), ["@org/api"]);
+        assert_eq!(config.resolve_team("frontend").unwrap(), ["@org/web", "@org/mobile"]);
+
+        assert!(ConfigFile::parse("colour = \"never\"").is_err());
+    }
+
+    #[test]
+    fn test_alias_cycles() {
+        let file = ConfigFile::parse(
+            r#"
+            [aliases]
+            a = ["@org/web", "b"]
+            b = ["c"]
+            c = "a"
+            "#,
+        )
+        .unwrap();
+
+        let path = Path::new("/repo/teamsearch.toml");
+        let mut config = Config::default();
+        config.merge(file, path, Source::Project(path.into()));
+
+        let err = config.resolve_team("b").unwrap_err().to_string();
+        assert!(err.contains("b -> c -> a -> b"), "{err}");
+    }
+
+    #[test]
+    fn test_alias_of_same_name() {
+        let config = |contents: &str| {
+            let path = Path::new("/repo/teamsearch.toml");
+            let mut config = Config::default();
+            config.merge(ConfigFile::parse(contents).unwrap(), path, Source::Project(path.into()));
+            config
+        };
+
+        // An alias may stand for the team of the same name.
+        let same = config("[aliases]\nweb = \"@web\"");
+        assert_eq!(same.resolve_team("web").unwrap(), ["@web"]);
+
+        // A group can name both the team and the alias of the same name.
+        let group = config("[aliases]\nweb = \"@org/web\"\nfrontend = [\"@web\", \"web\"]");
+        assert_eq!(group.resolve_team("frontend").unwrap(), ["@web", "@org/web"]);
+    }
+}
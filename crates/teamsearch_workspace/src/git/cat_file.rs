@@ -0,0 +1,103 @@
+//! A long-running `git cat-file --batch` process, which reads the contents of
+//! many objects without spawning a new process for each of them.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use anyhow::{Result, anyhow, bail};
+
+#[derive(Debug)]
+pub(super) struct CatFile {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CatFile {
+    /// Start reading objects from the repository at `root`.
+    pub(super) fn spawn(root: &Path) -> Result<Self> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["cat-file", "--batch"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| anyhow!("Failed to run `git`: {err}."))?;
+
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(CatFile { child, stdin, stdout })
+    }
+
+    /// Read the contents of the blob that `object` names, returning `None` if
+    /// the object doesn't exist.
+    pub(super) fn read_blob(&mut self, object: &str) -> Result<Option<Vec<u8>>> {
+        // The objects are separated by newlines, so they can't contain any.
+        if object.contains('\n') {
+            bail!("Can't read the git object `{object}`.");
+        }
+
+        let stdin = self.stdin.as_mut().unwrap();
+        writeln!(stdin, "{object}")?;
+        stdin.flush()?;
+
+        // The header is either `<oid> <type> <size>` or `<object> missing`,
+        // where the object may itself contain spaces.
+        let mut header = String::new();
+        if self.stdout.read_line(&mut header)? == 0 {
+            bail!("`git cat-file` exited before reading `{object}`.");
+        }
+
+        let header = header.trim_end();
+        if header.ends_with(" missing") || header.ends_with(" ambiguous") {
+            return Ok(None);
+        }
+
+        let (kind, size) = match header.split(' ').collect::<Vec<_>>()[..] {
+            [_, kind, size] => (kind, size.parse::<usize>()?),
+            _ => bail!("Unexpected output from `git cat-file`: `{header}`."),
+        };
+
+        // The contents are always followed by a newline.
+        let mut contents = vec![0; size + 1];
+        self.stdout.read_exact(&mut contents)?;
+        contents.pop();
+
+        Ok((kind == "blob").then_some(contents))
+    }
+}
+
+impl Drop for CatFile {
+    fn drop(&mut self) {
+        // Closing the input makes `git` exit once it's done.
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::repository;
+
+    #[test]
+    fn test_read_blob() {
+        let repo = repository(&[("a.rs", "a\n"), ("dir with space/b.rs", "b")]);
+        let mut cat_file = CatFile::spawn(repo.path()).unwrap();
+
+        assert_eq!(cat_file.read_blob("HEAD:a.rs").unwrap().unwrap(), b"a\n");
+        assert_eq!(cat_file.read_blob("HEAD:dir with space/b.rs").unwrap().unwrap(), b"b");
+        assert!(cat_file.read_blob("HEAD:dir with space/c.rs").unwrap().is_none());
+
+        // Trees aren't blobs, but their contents must still be consumed.
+        assert!(cat_file.read_blob("HEAD:dir with space").unwrap().is_none());
+        assert_eq!(cat_file.read_blob("HEAD:a.rs").unwrap().unwrap(), b"a\n");
+
+        assert!(cat_file.read_blob("HEAD:a\nb").is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! A reader for the git index (`.git/index`), which lists all of the files
+//! that are tracked by a repository. The index is parsed directly, rather than
+//! through the `git` command line, since it's only a single file read and the
+//! format is stable.
+//!
+//! Versions 2, 3 and 4 of the format are supported, see
+//! <https://git-scm.com/docs/index-format> for the details.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow, bail};
+
+/// The signature at the start of every index file.
+const SIGNATURE: &[u8; 4] = b"DIRC";
+
+/// The size of the fixed part of an entry, up to and including the flags, in
+/// a repository that uses SHA-1 object names.
+const ENTRY_SIZE: usize = 62;
+
+/// The flag that marks an entry as having the extended flags (version 3+).
+const EXTENDED_FLAG: u16 = 1 << 14;
+
+/// The extended flag of an entry that isn't present in the working tree, e.g.
+/// because of a sparse checkout.
+const SKIP_WORKTREE_FLAG: u16 = 1 << 14;
+
+/// The object type bits of the mode of an entry.
+const MODE_TYPE_MASK: u32 = 0o170000;
+
+/// The object type of regular files, and symbolic links.
+const MODE_REGULAR: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+
+/// Read the paths of the files that are tracked in the given index, relative
+/// to the root of the repository. Entries that don't refer to a file in the
+/// working tree, such as submodules and sparse directories, are omitted.
+pub fn read_index(path: &Path) -> Result<Vec<PathBuf>> {
+    let data = std::fs::read(path)
+        .map_err(|err| anyhow!("Failed to read the git index `{}`: {err}.", path.display()))?;
+    parse_index(&data)
+        .map_err(|err| anyhow!("Failed to parse the git index `{}`: {err}", path.display()))
+}
+
+/// Parse the contents of an index, see [read_index].
+fn parse_index(data: &[u8]) -> Result<Vec<PathBuf>> {
+    let mut reader = Reader { data, pos: 0 };
+
+    if reader.bytes(4)? != SIGNATURE {
+        bail!("not a git index.");
+    }
+
+    let version = reader.u32()?;
+    if !(2..=4).contains(&version) {
+        bail!("unsupported version {version}.");
+    }
+
+    let count = reader.u32()? as usize;
+    let mut paths = Vec::with_capacity(count);
+    let mut previous: Vec<u8> = Vec::new();
+
+    for _ in 0..count {
+        let start = reader.pos;
+        reader.bytes(24)?;
+        let mode = reader.u32()?;
+        reader.bytes(ENTRY_SIZE - 2 - 28)?;
+        let flags = reader.u16()?;
+
+        let extended = if version >= 3 && flags & EXTENDED_FLAG != 0 { reader.u16()? } else { 0 };
+
+        let path = if version == 4 {
+            // The path is compressed against the previous one, by removing a
+            // number of bytes from its end and appending the rest.
+            let strip = reader.varint()?;
+            if strip > previous.len() {
+                bail!("invalid path compression.");
+            }
+            previous.truncate(previous.len() - strip);
+            previous.extend_from_slice(reader.until_nul()?);
+            previous.clone()
+        } else {
+            let path = reader.until_nul()?.to_vec();
+
+            // Entries are padded with NULs to a multiple of eight bytes, and
+            // the terminating NUL has already been consumed.
+            let len = reader.pos - start;
+            reader.bytes(len.next_multiple_of(8) - len)?;
+            path
+        };
+
+        let kind = mode & MODE_TYPE_MASK;
+        if extended & SKIP_WORKTREE_FLAG == 0 && (kind == MODE_REGULAR || kind == MODE_SYMLINK) {
+            paths.push(path_from_bytes(path)?);
+        }
+    }
+
+    // A split index stores most of its entries in a shared index, which isn't
+    // worth supporting for the sake of listing files.
+    while reader.data.len() - reader.pos > 20 {
+        let signature = reader.bytes(4)?;
+        if signature == b"link" {
+            bail!("split indexes aren't supported.");
+        }
+
+        let size = reader.u32()? as usize;
+        reader.bytes(size)?;
+    }
+
+    // Entries that are in conflict appear once for each of their stages.
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Convert the raw bytes of a path, as git stores it, into a [PathBuf]. Paths
+/// aren't necessarily valid UTF-8 on Unix, but they must be elsewhere.
+#[cfg(unix)]
+pub(super) fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+pub(super) fn path_from_bytes(bytes: Vec<u8>) -> Result<PathBuf> {
+    String::from_utf8(bytes).map(PathBuf::from).map_err(|_| anyhow!("path isn't valid UTF-8."))
+}
+
+/// A cursor over the bytes of an index.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| anyhow!("unexpected end of file."))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    /// Read the bytes up to the next NUL, consuming the NUL itself.
+    fn until_nul(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|&b| b == 0).ok_or_else(|| anyhow!("unterminated path."))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    /// Read a variable width integer, as used for the path compression of
+    /// version 4 indexes.
+    fn varint(&mut self) -> Result<usize> {
+        let mut byte = self.bytes(1)?[0];
+        let mut value = (byte & 0x7f) as usize;
+        while byte & 0x80 != 0 {
+            byte = self.bytes(1)?[0];
+            value = ((value + 1) << 7) | (byte & 0x7f) as usize;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build an index of the given version with regular file entries.
+    fn index(version: u32, paths: &[&str]) -> Vec<u8> {
+        let mut data = SIGNATURE.to_vec();
+        data.extend(version.to_be_bytes());
+        data.extend((paths.len() as u32).to_be_bytes());
+
+        let mut previous = "";
+        for path in paths {
+            let start = data.len();
+            data.extend([0; 24]);
+            data.extend(MODE_REGULAR.to_be_bytes());
+            data.extend([0; ENTRY_SIZE - 2 - 28]);
+            data.extend((path.len().min(0xfff) as u16).to_be_bytes());
+
+            if version == 4 {
+                let common = previous.bytes().zip(path.bytes()).take_while(|(a, b)| a == b).count();
+                data.push((previous.len() - common) as u8);
+                data.extend(&path.as_bytes()[common..]);
+                data.push(0);
+            } else {
+                data.extend(path.as_bytes());
+                let len = data.len() - start;
+                data.extend(vec![0; 8 - len % 8]);
+            }
+            previous = path;
+        }
+
+        data.extend([0; 20]);
+        data
+    }
+
+    #[test]
+    fn test_parse_index() {
+        let paths = ["README.md", "src/lib.rs", "src/main.rs"];
+        let expected = paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+        for version in [2, 3, 4] {
+            assert_eq!(parse_index(&index(version, &paths)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_index() {
+        assert!(parse_index(b"DIRX").is_err());
+
+        let mut data = index(2, &["a.rs"]);
+        data.truncate(20);
+        assert!(parse_index(&data).is_err());
+    }
+}
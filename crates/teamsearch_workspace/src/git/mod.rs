@@ -0,0 +1,508 @@
+//! Utilities for querying the git repository that the files belong to. Most of
+//! these use the `git` command line, so that the behaviour matches exactly
+//! what the user sees when they run `git` themselves. The tracked files are
+//! read directly from the [index] instead.
+
+mod cat_file;
+mod index;
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{Result, anyhow};
+use cat_file::CatFile;
+use itertools::Itertools;
+use log::warn;
+use teamsearch_utils::fs;
+
+/// The set of changes to collect the files of.
+#[derive(Debug, Clone, Copy)]
+pub enum Changes<'a> {
+    /// All of the changes since the given revision, including the changes that
+    /// haven't been committed, and the files that aren't tracked yet.
+    Since(&'a str),
+
+    /// Only the changes that are staged.
+    Staged,
+
+    /// Only the files that are added, or renamed, by the staged changes.
+    Added,
+}
+
+/// Run `git` with the given arguments within `dir`, returning its output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    Ok(String::from_utf8_lossy(&git_bytes(dir, args)?).into_owned())
+}
+
+/// Run `git` with the given arguments within `dir`, returning its raw output.
+fn git_bytes(dir: &Path, args: &[impl AsRef<OsStr>]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|err| anyhow!("Failed to run `git`: {err}."))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let args = args.iter().map(|arg| arg.as_ref().to_string_lossy()).join(" ");
+        return Err(anyhow!("Failed to run `git {args}`: {}", stderr.trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Get the root directory of the repository that contains `dir`. The root is
+/// found relative to `dir` rather than taken from `--show-toplevel`, which
+/// resolves symbolic links, so that it matches the paths of the walked files
+/// when the repository is checked out through a symbolic link.
+pub fn repository_root(dir: &Path) -> Result<PathBuf> {
+    let cdup = git(dir, &["rev-parse", "--show-cdup"])?;
+    Ok(fs::normalize_path(dir.join(cdup.trim_end())))
+}
+
+/// Get the absolute paths of the files that have been added or modified by
+/// the given `changes`, within the repository at `root`, see
+/// [repository_root]. Files that have been deleted are omitted, since there's
+/// nothing left to look at.
+pub fn changed_files(root: &Path, changes: Changes<'_>) -> Result<Vec<PathBuf>> {
+    let mut paths = match changes {
+        Changes::Since(rev) => {
+            // The revision is given by the user, so it must never be read
+            // as an option, e.g. `--output=<file>`.
+            let args =
+                ["diff", "--name-only", "-z", "--diff-filter=d", "--end-of-options", rev, "--"];
+            let mut paths = git(root, &args)?;
+            paths += &git(root, &["ls-files", "--others", "--exclude-standard", "-z"])?;
+            paths
+        }
+        Changes::Staged => {
+            git(root, &["diff", "--name-only", "-z", "--diff-filter=d", "--cached"])?
+        }
+        Changes::Added => {
+            git(root, &["diff", "--name-only", "-z", "--diff-filter=AR", "--cached"])?
+        }
+    }
+    .split('\0')
+    .filter(|path| !path.is_empty())
+    .map(|path| root.join(path))
+    .collect::<Vec<_>>();
+
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+}
+
+/// Find the root of the working tree that contains `dir`, along with its git
+/// directory. The `.git` of a worktree or a submodule is a file that points to
+/// the actual git directory.
+fn discover(dir: &Path) -> Result<(PathBuf, PathBuf)> {
+    for root in dir.ancestors() {
+        let dot_git = root.join(".git");
+        if dot_git.is_dir() {
+            return Ok((root.to_path_buf(), dot_git));
+        }
+
+        if dot_git.is_file() {
+            let contents = std::fs::read_to_string(&dot_git)?;
+            let git_dir = contents
+                .strip_prefix("gitdir:")
+                .ok_or_else(|| anyhow!("Invalid git file `{}`.", dot_git.display()))?;
+            return Ok((root.to_path_buf(), root.join(git_dir.trim())));
+        }
+    }
+
+    Err(anyhow!("`{}` isn't within a git repository.", dir.display()))
+}
+
+/// Get the absolute paths of all of the files that are tracked by the
+/// repository that contains `dir`, as recorded in its index.
+pub fn tracked_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let (root, git_dir) = discover(dir)?;
+    let files = index::read_index(&git_dir.join("index"))?;
+    Ok(files.into_iter().map(|file| root.join(file)).collect())
+}
+
+/// Read the contents of the file at the absolute `path` as it's staged in the
+/// index of the repository at `root`, which includes any changes to it that
+/// are staged. If the file isn't tracked, `None` is returned.
+pub fn read_staged(root: &Path, path: &Path) -> Result<Option<String>> {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return Ok(None);
+    };
+
+    let relative = relative.to_string_lossy();
+    if git(root, &["ls-files", "-z", "--", &relative])?.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(git(root, &["cat-file", "blob", &format!(":{relative}")])?))
+}
+
+/// A version of a file, as it was after a commit that changed it, see
+/// [file_history].
+#[derive(Debug, Clone)]
+pub struct FileVersion {
+    /// The name of the commit.
+    pub commit: String,
+
+    /// The date of the commit, as `YYYY-MM-DD`.
+    pub date: String,
+
+    /// The name of the author of the commit.
+    pub author: String,
+
+    /// The first line of the message of the commit.
+    pub summary: String,
+
+    /// The contents of the file, or `None` if the commit deleted it.
+    pub contents: Option<String>,
+}
+
+/// Get each of the versions of the file at the absolute `path`, from the
+/// oldest to the newest, within the history of the repository at `root`. The
+/// file is followed across renames, and only the first parent of merges is
+/// followed, so that the versions are those that the branch actually had
+/// rather than those of the branches that were merged into it.
+pub fn file_history(root: &Path, path: &Path) -> Result<Vec<FileVersion>> {
+    let relative = path.strip_prefix(root).map_err(|_| {
+        anyhow!("`{}` isn't within the repository `{}`.", path.display(), root.display())
+    })?;
+    let relative = relative.to_string_lossy();
+
+    // Each commit starts with a NUL, followed by the commit and then the path
+    // that the file had at the commit, since it might have been renamed.
+    let log = git(
+        root,
+        &[
+            "-c",
+            "core.quotePath=false",
+            "log",
+            "--follow",
+            "--first-parent",
+            "--name-only",
+            "--date=short",
+            "--format=%x00%H%x09%ad%x09%an%x09%s",
+            "--",
+            &relative,
+        ],
+    )?;
+
+    // The versions are all read through the same process, rather than one
+    // for each of the commits. `--reverse` doesn't work together with
+    // `--follow`, so the commits are reversed here instead.
+    let mut cat_file = CatFile::spawn(root)?;
+    log.split('\0')
+        .filter_map(|entry| {
+            let mut lines = entry.lines();
+            let (commit, date, author, summary) = lines.next()?.splitn(4, '\t').collect_tuple()?;
+            let path = lines.find(|line| !line.is_empty()).unwrap_or(&relative);
+            Some((commit, date, author, summary, path))
+        })
+        .rev()
+        .map(|(commit, date, author, summary, path)| {
+            let contents = cat_file
+                .read_blob(&format!("{commit}:{path}"))?
+                .map(|contents| String::from_utf8_lossy(&contents).into_owned());
+
+            Ok(FileVersion {
+                commit: commit.to_string(),
+                date: date.to_string(),
+                author: author.to_string(),
+                summary: summary.to_string(),
+                contents,
+            })
+        })
+        .collect()
+}
+
+/// A commit that touched some of the files, see [commits].
+#[derive(Debug, Clone)]
+pub struct Commit {
+    /// The email address of the author of the commit.
+    pub email: String,
+
+    /// The name of the author of the commit.
+    pub name: String,
+
+    /// The absolute paths of the files that the commit touched.
+    pub files: Vec<PathBuf>,
+}
+
+/// Get the commits that touched any of the given `paths`, within the
+/// repository that contains `dir`. Merge commits are omitted, since they
+/// don't say much about who maintains the files, and if `since` is given
+/// only the commits after that date are returned.
+pub fn commits(dir: &Path, paths: &[PathBuf], since: Option<&str>) -> Result<Vec<Commit>> {
+    let root = repository_root(dir)?;
+
+    // Each commit starts with a NUL, followed by the author and then the names
+    // of the files that it touched on separate lines. The paths aren't
+    // necessarily valid UTF-8, so both they and the output are kept as bytes.
+    let mut args = [
+        "-c",
+        "core.quotePath=false",
+        "log",
+        "--no-merges",
+        "--no-renames",
+        "--format=%x00%ae%x09%an",
+        "--name-only",
+    ]
+    .map(OsStr::new)
+    .to_vec();
+    let since = since.map(|since| format!("--since={since}"));
+    args.extend(since.as_deref().map(OsStr::new));
+    args.push(OsStr::new("--"));
+    args.extend(paths.iter().map(|path| path.as_os_str()));
+
+    let output = git_bytes(&root, &args)?;
+    let mut commits = vec![];
+    for commit in output.split(|byte| *byte == b'\0') {
+        let mut lines = commit.split(|byte| *byte == b'\n');
+        let Some(author) = lines.next().map(String::from_utf8_lossy) else { continue };
+        let Some((email, name)) = author.split_once('\t') else { continue };
+
+        let mut files = vec![];
+        for file in lines.filter(|line| !line.is_empty()) {
+            match index::path_from_bytes(file.to_vec()) {
+                Ok(file) => files.push(root.join(file)),
+                Err(err) => warn!("Skipping a file touched by {name}: {err}"),
+            }
+        }
+
+        commits.push(Commit { email: email.to_string(), name: name.to_string(), files });
+    }
+
+    Ok(commits)
+}
+
+/// A commit of a repository, whose files are read from the objects of the
+/// repository rather than from the working tree, so that there's no need to
+/// check it out.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// The root directory of the working tree of the repository.
+    root: PathBuf,
+
+    /// The name of the commit that the revision resolved to.
+    commit: String,
+
+    /// The process that reads the contents of the files, which is only
+    /// started once the first file is read.
+    cat_file: Arc<Mutex<Option<CatFile>>>,
+
+    /// The size of each of the files of the revision by their absolute path,
+    /// which is only listed once it's first needed.
+    tree: Arc<OnceLock<BTreeMap<PathBuf, u64>>>,
+}
+
+impl Revision {
+    /// Resolve the revision `rev` within the repository that contains `dir`.
+    pub fn new(dir: &Path, rev: &str) -> Result<Self> {
+        let root = repository_root(dir)?;
+        let commit = git(
+            &root,
+            &["rev-parse", "--verify", "--end-of-options", &format!("{rev}^{{commit}}")],
+        )
+        .map_err(|_| anyhow!("Unknown git revision `{rev}`."))?;
+
+        let commit = commit.trim_end().to_string();
+        Ok(Revision { root, commit, cat_file: Arc::default(), tree: Arc::default() })
+    }
+
+    /// Get the name of the object of `path` within the revision.
+    fn object(&self, path: &Path) -> Result<String> {
+        let relative = path.strip_prefix(&self.root).map_err(|_| {
+            anyhow!("`{}` isn't within the repository `{}`.", path.display(), self.root.display())
+        })?;
+
+        Ok(format!("{}:{}", self.commit, relative.to_string_lossy()))
+    }
+
+    /// Read the contents of the file at the absolute `path`, as it is in the
+    /// revision.
+    pub fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        let object = self.object(path)?;
+
+        let mut guard = self.cat_file.lock().unwrap();
+        let cat_file = match &mut *guard {
+            Some(cat_file) => cat_file,
+            None => guard.insert(CatFile::spawn(&self.root)?),
+        };
+
+        // If the process failed part way through an object, the rest of its
+        // output can't be trusted, so a new one is started for the next file.
+        let contents = cat_file.read_blob(&object).inspect_err(|_| *guard = None)?;
+        contents.ok_or_else(|| anyhow!("`{}` doesn't exist at `{}`.", path.display(), self.commit))
+    }
+
+    /// Read the contents of the file at the absolute `path`, as it is in the
+    /// revision, as a string.
+    pub fn read_to_string(&self, path: &Path) -> Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|_| anyhow!("`{}` isn't valid UTF-8.", path.display()))
+    }
+
+    /// Get the size of the file at the absolute `path` in bytes, as it is in
+    /// the revision.
+    pub fn size(&self, path: &Path) -> Result<u64> {
+        self.tree()?
+            .get(path)
+            .copied()
+            .ok_or_else(|| anyhow!("`{}` doesn't exist at `{}`.", path.display(), self.commit))
+    }
+
+    /// Get the absolute paths of all of the files within the revision.
+    /// Submodules are omitted, since their contents aren't part of the tree.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.tree()?.keys().cloned().collect())
+    }
+
+    /// Get the size of each of the files within the revision, which are all
+    /// listed at once rather than asking `git` for each of them.
+    fn tree(&self) -> Result<&BTreeMap<PathBuf, u64>> {
+        if let Some(tree) = self.tree.get() {
+            return Ok(tree);
+        }
+
+        let output = git(&self.root, &["ls-tree", "-r", "-l", "-z", "--full-tree", &self.commit])?;
+
+        // Each entry is of the form `<mode> <type> <object> <size>\t<path>`,
+        // where the size is padded with spaces.
+        let tree = output
+            .split('\0')
+            .filter_map(|entry| entry.split_once('\t'))
+            .filter_map(|(info, path)| match info.split_whitespace().collect_vec()[..] {
+                [_, "blob", _, size] => Some((self.root.join(path), size.parse().ok()?)),
+                _ => None,
+            })
+            .collect();
+
+        Ok(self.tree.get_or_init(|| tree))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::testing::{commit, git as run, repository};
+
+    #[test]
+    fn test_changed_files() {
+        let repo = repository(&[("a.rs", "a"), ("b.rs", "b"), ("src/c.rs", "c")]);
+        let root = repo.path();
+
+        fs::write(root.join("a.rs"), "changed").unwrap();
+        fs::remove_file(root.join("b.rs")).unwrap();
+        fs::write(root.join("untracked.rs"), "").unwrap();
+        run(root, &["mv", "src/c.rs", "src/d.rs"]);
+
+        let changed = |changes| changed_files(root, changes).unwrap();
+        assert_eq!(
+            changed(Changes::Since("HEAD")),
+            ["a.rs", "src/d.rs", "untracked.rs"].map(|path| root.join(path))
+        );
+        assert_eq!(changed(Changes::Staged), [root.join("src/d.rs")]);
+        assert_eq!(changed(Changes::Added), [root.join("src/d.rs")]);
+
+        // A revision that looks like an option isn't read as one.
+        let output = root.join("output");
+        let rev = format!("--output={}", output.display());
+        assert!(changed_files(root, Changes::Since(&rev)).is_err());
+        assert!(!output.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_repository_root_through_symlink() {
+        let repo = repository(&[("a.rs", "a")]);
+        let link = TempDir::new().unwrap();
+        let checkout = link.path().join("checkout");
+        std::os::unix::fs::symlink(repo.path(), &checkout).unwrap();
+
+        fs::write(checkout.join("a.rs"), "changed").unwrap();
+        assert_eq!(repository_root(&checkout).unwrap(), checkout);
+        assert_eq!(
+            changed_files(&repository_root(&checkout).unwrap(), Changes::Since("HEAD")).unwrap(),
+            [checkout.join("a.rs")]
+        );
+    }
+
+    #[test]
+    fn test_revision() {
+        let repo = repository(&[("a.rs", "a"), ("src/b.rs", "bb")]);
+        let root = repo.path();
+        commit(root, &[("a.rs", "changed"), ("c.rs", "c")], "Change a");
+
+        let revision = Revision::new(&root.join("src"), "HEAD~1").unwrap();
+        assert_eq!(revision.files().unwrap(), [root.join("a.rs"), root.join("src/b.rs")]);
+        assert_eq!(revision.read(&root.join("a.rs")).unwrap(), b"a");
+        assert_eq!(revision.read_to_string(&root.join("src/b.rs")).unwrap(), "bb");
+        assert_eq!(revision.size(&root.join("src/b.rs")).unwrap(), 2);
+
+        assert!(revision.read(&root.join("c.rs")).is_err());
+        assert!(revision.size(&root.join("c.rs")).is_err());
+        assert!(revision.read(Path::new("/elsewhere/a.rs")).is_err());
+        assert!(Revision::new(root, "no-such-branch").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_commits_with_non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let repo = repository(&[("a.rs", "a")]);
+        let root = repo.path();
+
+        let name = OsStr::from_bytes(b"caf\xe9.rs");
+        fs::write(root.join(name), "").unwrap();
+        commit(root, &[("a.rs", "changed")], "Add a file");
+
+        let commits = commits(root, &[root.join(name), root.join("a.rs")], None).unwrap();
+        let files = commits.iter().map(|commit| commit.files.clone()).collect_vec();
+        assert_eq!(files, [vec![root.join("a.rs"), root.join(name)], vec![root.join("a.rs")]]);
+    }
+
+    #[test]
+    fn test_file_history() {
+        let repo = repository(&[("CODEOWNERS", "1")]);
+        let root = repo.path();
+        commit(root, &[("CODEOWNERS", "2"), ("a.rs", "")], "Change the owners");
+        commit(root, &[("a.rs", "changed")], "Change a file");
+
+        // Changes on other branches are only seen through their merge.
+        run(root, &["checkout", "-q", "-b", "other"]);
+        commit(root, &[("CODEOWNERS", "3")], "Change the owners on a branch");
+        run(root, &["checkout", "-q", "-"]);
+        run(root, &["merge", "-q", "--no-ff", "-m", "Merge the branch", "other"]);
+
+        fs::create_dir(root.join(".github")).unwrap();
+        run(root, &["mv", "CODEOWNERS", ".github/CODEOWNERS"]);
+        run(root, &["commit", "-q", "-m", "Move the owners"]);
+        commit(root, &[(".github/CODEOWNERS", "4")], "Change the moved owners");
+
+        let history = file_history(root, &root.join(".github/CODEOWNERS")).unwrap();
+        let versions = history.iter().map(|version| {
+            (version.summary.as_str(), version.contents.as_deref(), version.author.as_str())
+        });
+        assert_eq!(
+            versions.collect_vec(),
+            [
+                ("Initial commit", Some("1"), "Carol"),
+                ("Change the owners", Some("2"), "Carol"),
+                ("Merge the branch", Some("3"), "Carol"),
+                ("Move the owners", Some("3"), "Carol"),
+                ("Change the moved owners", Some("4"), "Carol"),
+            ]
+        );
+    }
+}
@@ -4,9 +4,16 @@
 //! responsible for creating a [Workspace] instance.
 
 pub mod codeowners;
+pub mod config;
+pub mod git;
 mod member;
+pub mod registry;
 pub mod resolver;
+pub mod roster;
 pub mod settings;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod types;
 
 use std::{collections::HashMap, path::PathBuf};
 
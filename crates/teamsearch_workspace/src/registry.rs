@@ -0,0 +1,163 @@
+//! Implementation of the team registry, which records how to reach each of the
+//! owners of the CODEOWNERS file. The registry is a YAML, JSON or TOML file
+//! (depending on its extension) that maps each owner to its metadata:
+//!
+//! ```yaml
+//! "@org/web":
+//!   name: Web Platform
+//!   channel: "#web-platform"
+//!   on_call: "@web-oncall"
+//!   email: web@example.com
+//!   members: [alice@example.com, bob@example.com]
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Result, anyhow};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// The metadata of an owner, all of which is optional.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamInfo {
+    /// The display name of the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// The chat channel of the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+
+    /// The alias that reaches whoever is on call for the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_call: Option<String>,
+
+    /// The email address of the owner.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+
+    /// The members of the owner, if it's a team.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub members: Vec<String>,
+}
+
+impl TeamInfo {
+    /// A short description of how to reach the owner, e.g. `Web Platform,
+    /// #web-platform, on-call @web-oncall, web@example.com`. The members
+    /// aren't included, since there might be many of them.
+    pub fn summary(&self) -> String {
+        let on_call = self.on_call.as_ref().map(|on_call| format!("on-call {on_call}"));
+        [self.name.clone(), self.channel.clone(), on_call, self.email.clone()]
+            .into_iter()
+            .flatten()
+            .join(", ")
+    }
+}
+
+/// The format of a registry file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+#[derive(Debug, Default)]
+pub struct Registry {
+    /// The metadata of each owner, keyed by the lowercased owner, since the
+    /// names of teams and users aren't case sensitive.
+    owners: HashMap<String, TeamInfo>,
+}
+
+impl Registry {
+    /// Read and parse the registry at the given path, where the format is
+    /// decided by the extension of the file.
+    pub fn parse_from_file(path: &Path) -> Result<Self> {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("yaml" | "yml") => RegistryFormat::Yaml,
+            Some("json") => RegistryFormat::Json,
+            Some("toml") => RegistryFormat::Toml,
+            _ => {
+                return Err(anyhow!(
+                    "Unknown format of the team registry at {path:?}, expected a `.yaml`, `.json` or `.toml` file."
+                ));
+            }
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("Failed to read the team registry at {path:?}: {err}."))?;
+        Self::parse(&contents, format)
+            .map_err(|err| anyhow!("Invalid team registry at {path:?}: {}", err.to_string().trim()))
+    }
+
+    /// Parse the `contents` of a registry in the given `format`.
+    pub fn parse(contents: &str, format: RegistryFormat) -> Result<Self> {
+        let owners: HashMap<String, TeamInfo> = match format {
+            RegistryFormat::Yaml => serde_yaml_ng::from_str(contents)?,
+            RegistryFormat::Json => serde_json::from_str(contents)?,
+            RegistryFormat::Toml => toml::from_str(contents)?,
+        };
+
+        let owners = owners.into_iter().map(|(owner, info)| (owner.to_lowercase(), info));
+        Ok(Registry { owners: owners.collect() })
+    }
+
+    /// Get the metadata of the given owner, if it's in the registry.
+    pub fn get(&self, owner: &str) -> Option<&TeamInfo> {
+        self.owners.get(&owner.to_lowercase())
+    }
+
+    /// Describe the given owner, followed by its metadata if it's in the
+    /// registry, e.g. `@org/web (Web Platform, #web-platform)`.
+    pub fn describe(&self, owner: &str) -> String {
+        match self.get(owner).map(TeamInfo::summary) {
+            Some(summary) if !summary.is_empty() => format!("{owner} ({summary})"),
+            _ => owner.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_formats() {
+        let yaml = r##"
+"@org/web":
+  name: Web Platform
+  channel: "#web"
+  on_call: "@web-oncall"
+  members: [alice@example.com]
+"@org/api": {}
+"##;
+        let json = r##"{ "@Org/Web": { "name": "Web Platform", "channel": "#web", "on_call": "@web-oncall" }, "@org/api": {} }"##;
+        let toml = r##"
+["@org/web"]
+name = "Web Platform"
+channel = "#web"
+on_call = "@web-oncall"
+
+["@org/api"]
+"##;
+
+        for (contents, format) in [
+            (yaml, RegistryFormat::Yaml),
+            (json, RegistryFormat::Json),
+            (toml, RegistryFormat::Toml),
+        ] {
+            let registry = Registry::parse(contents, format).unwrap();
+            assert_eq!(
+                registry.describe("@org/web"),
+                "@org/web (Web Platform, #web, on-call @web-oncall)"
+            );
+            assert_eq!(registry.describe("@org/api"), "@org/api");
+            assert!(registry.get("@org/core").is_none());
+        }
+
+        assert!(
+            Registry::parse("\"@org/web\": { slack: \"#web\" }", RegistryFormat::Yaml).is_err()
+        );
+    }
+}
@@ -4,27 +4,32 @@
 use std::{
     cmp::Ordering,
     ffi::OsStr,
+    ops::ControlFlow,
     path::{Path, PathBuf},
-    sync::RwLock,
+    sync::{Mutex, RwLock},
 };
 
 use anyhow::{Result, anyhow};
 use globset::{Candidate, GlobSet};
 use ignore::{DirEntry, Error, WalkBuilder, WalkState};
 use itertools::Itertools;
-use log::debug;
+use log::{debug, warn};
 use teamsearch_utils::fs;
 
-use crate::settings::Settings;
+use crate::{codeowners::CodeOwnersTree, git::Revision, settings::Settings};
 
 // @@Todo: add a way to add exclusions to the search.
 pub struct Resolver<'a> {
     settings: &'a Settings,
+
+    /// The CODEOWNERS files that nested repositories are registered with as
+    /// they're found, if any.
+    codeowners: Option<&'a CodeOwnersTree>,
 }
 
 impl<'a> Resolver<'a> {
-    pub fn new(settings: &'a Settings) -> Self {
-        Resolver { settings }
+    pub fn new(settings: &'a Settings, codeowners: Option<&'a CodeOwnersTree>) -> Self {
+        Resolver { settings, codeowners }
     }
 
     /// Check whether we should respect `.gitignore` files.
@@ -46,21 +51,65 @@ pub fn find_files_in_paths(
     root: &PathBuf,
     paths: &[PathBuf],
     settings: &Settings,
+    codeowners: Option<&CodeOwnersTree>,
 ) -> Result<ResolvedFiles> {
+    let files = Mutex::new(Vec::new());
+
+    visit_files_in_paths(root, paths, settings, codeowners, |file| {
+        files.lock().unwrap().push(file);
+        ControlFlow::Continue(())
+    })?;
+
+    Ok(files.into_inner().unwrap())
+}
+
+/// Walk the given paths, and call `visit` with each of the files that are
+/// found as soon as they're found, from the threads of the walker. The walk
+/// stops as soon as `visit` returns [ControlFlow::Break], which avoids walking
+/// the rest of the paths when the caller has seen enough files.
+///
+/// If `codeowners` is given, the nested repositories that have a CODEOWNERS
+/// file of their own are registered with it as they're found, before any of
+/// their files are visited.
+pub fn visit_files_in_paths<F>(
+    root: &PathBuf,
+    paths: &[PathBuf],
+    settings: &Settings,
+    codeowners: Option<&CodeOwnersTree>,
+    visit: F,
+) -> Result<()>
+where
+    F: Fn(Result<ResolvedFile, ignore::Error>) -> ControlFlow<()> + Sync,
+{
     // Create a resolver, and then use it to aid in the search for files.
-    let resolver = Resolver::new(settings);
+    let resolver = Resolver::new(settings, codeowners);
 
     // Normalize every path (e.g., convert from relative to absolute).
     let mut paths: Vec<PathBuf> = paths.iter().map(fs::normalize_path).unique().collect();
 
+    // The walk only finds the nested repositories within the paths, so the
+    // ones that contain the paths themselves are found up front. The files of
+    // a git revision aren't on the file system, so there's nothing to find.
+    if let Some(codeowners) = codeowners.filter(|_| settings.revision.is_none()) {
+        for path in &paths {
+            codeowners.discover_ancestors(path)?;
+        }
+    }
+
     // Check if the paths themselves are excluded.
     if resolver.force_exclude() {
         paths.retain(|path| !is_file_excluded(path, &resolver));
         if paths.is_empty() {
-            return Ok(vec![]);
+            return Ok(());
         }
     }
 
+    // The files of a git revision aren't on the file system, so they're
+    // selected from the tree of the revision instead of being walked.
+    if let Some(revision) = &settings.revision {
+        return visit_revision_files(revision, root, &paths, &resolver, &visit);
+    }
+
     let (first_path, rest_paths) = paths
         .split_first()
         .ok_or_else(|| anyhow!("Expected at least one path to search for files"))?;
@@ -74,39 +123,32 @@ pub fn find_files_in_paths(
     builder.hidden(false);
     builder.standard_filters(resolver.respect_gitignore());
 
-    builder.threads(
-        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get).min(12),
-    );
+    builder.threads(settings.threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get).min(12)
+    }));
 
     let walker = builder.build_parallel();
 
-    let state = WalkFilesState::new(resolver);
+    let state = WalkFilesState::new(resolver, &visit);
     let mut visitor = FilesVisitorBuilder::new(&state, root);
     walker.visit(&mut visitor);
 
-    state.finish()
+    Ok(())
 }
 
 type ResolvedFiles = Vec<Result<ResolvedFile, ignore::Error>>;
 
+/// The callback that is invoked for each of the files that are found.
+type VisitFn<'a> = dyn Fn(Result<ResolvedFile, ignore::Error>) -> ControlFlow<()> + Sync + 'a;
+
 pub struct WalkFilesState<'a> {
     resolver: RwLock<Resolver<'a>>,
-    merged: std::sync::Mutex<(ResolvedFiles, Result<()>)>,
+    visit: &'a VisitFn<'a>,
 }
 
 impl<'a> WalkFilesState<'a> {
-    pub fn new(resolver: Resolver<'a>) -> Self {
-        WalkFilesState {
-            resolver: RwLock::new(resolver),
-            merged: std::sync::Mutex::new((Vec::new(), Ok(()))),
-        }
-    }
-
-    fn finish(self) -> Result<ResolvedFiles> {
-        let (files, error) = self.merged.into_inner().unwrap();
-        error?;
-
-        Ok(files)
+    pub fn new(resolver: Resolver<'a>, visit: &'a VisitFn<'a>) -> Self {
+        WalkFilesState { resolver: RwLock::new(resolver), visit }
     }
 }
 
@@ -126,18 +168,11 @@ where
     'config: 's,
 {
     fn build(&mut self) -> Box<dyn ignore::ParallelVisitor + 's> {
-        Box::new(FilesVisitor {
-            local_files: vec![],
-            local_error: Ok(()),
-            global: self.state,
-            base: self.base,
-        })
+        Box::new(FilesVisitor { global: self.state, base: self.base })
     }
 }
 
 pub struct FilesVisitor<'s, 'config> {
-    local_files: Vec<Result<ResolvedFile, ignore::Error>>,
-    local_error: Result<()>,
     global: &'s WalkFilesState<'config>,
     base: &'s PathBuf,
 }
@@ -152,30 +187,27 @@ impl ignore::ParallelVisitor for FilesVisitor<'_, '_> {
             let resolver = self.global.resolver.read().unwrap();
             let settings = resolver.resolve(path);
 
-            if let Some(file_name) = path.file_name() {
-                let file_path = Candidate::new(path);
-                let file_basename = Candidate::new(file_name);
-
-                if match_candidate_exclusion(
-                    &file_path,
-                    &file_basename,
-                    &settings.file_resolver.exclude,
-                ) {
-                    return WalkState::Skip;
-                }
+            // Skip the directories that don't contain any of the files that
+            // we're restricted to.
+            if let Some(subset) = &settings.file_resolver.subset
+                && entry.file_type().is_some_and(|ft| ft.is_dir())
+                && !subset.contains_dir(entry.path())
+            {
+                return WalkState::Skip;
+            }
 
-                // @@Todo: we should combine `exclude` and `user_exclude` into a single
-                // exclusion set.
-                if match_candidate_exclusion(
-                    &file_path,
-                    &file_basename,
-                    &settings.file_resolver.user_exclude,
-                ) {
-                    return WalkState::Skip;
-                }
-            } else {
+            if path.file_name().is_none() || is_entry_excluded(path, settings) {
                 return WalkState::Skip;
             }
+
+            // Directories might be the roots of nested repositories, whose
+            // files are governed by their own CODEOWNERS file.
+            if let Some(codeowners) = resolver.codeowners
+                && entry.file_type().is_some_and(|ft| ft.is_dir())
+                && let Err(err) = codeowners.discover(entry.path())
+            {
+                warn!("Ignoring the CODEOWNERS file of {:?}: {err}", entry.path());
+            }
         }
 
         match result {
@@ -192,42 +224,113 @@ impl ignore::ParallelVisitor for FilesVisitor<'_, '_> {
                     let resolver = self.global.resolver.read().unwrap();
                     let settings = resolver.resolve(path);
 
-                    if settings.file_resolver.include.is_match(path) {
+                    if is_selected(path, settings) {
                         Some(ResolvedFile::Nested(entry.into_path()))
                     } else {
                         None
                     }
                 };
 
-                if let Some(resolved) = resolved {
-                    self.local_files.push(Ok(resolved));
+                // Even the files that are passed-in directly must be part of
+                // the subset of files, if there is one.
+                let resolved = resolved.filter(|file| {
+                    let resolver = self.global.resolver.read().unwrap();
+                    let subset = &resolver.resolve(file.path()).file_resolver.subset;
+                    subset.as_ref().is_none_or(|subset| subset.contains(file.path()))
+                });
+
+                match resolved {
+                    Some(resolved) => visit_state((self.global.visit)(Ok(resolved))),
+                    None => WalkState::Continue,
                 }
             }
-            Err(err) => {
-                self.local_files.push(Err(err));
-            }
+            Err(err) => visit_state((self.global.visit)(Err(err))),
         }
-
-        WalkState::Continue
     }
 }
 
-impl Drop for FilesVisitor<'_, '_> {
-    fn drop(&mut self) {
-        let mut merged = self.global.merged.lock().unwrap();
-        let (files, error) = &mut *merged;
-
-        if files.is_empty() {
-            *files = std::mem::take(&mut self.local_files);
+/// Visit the files of a git revision that are within the given `paths`,
+/// selecting them in the same way as the walk of the file system does.
+fn visit_revision_files(
+    revision: &Revision,
+    base: &Path,
+    paths: &[PathBuf],
+    resolver: &Resolver,
+    visit: &VisitFn,
+) -> Result<()> {
+    for file in revision.files()? {
+        let Some(path) = paths.iter().find(|path| file.starts_with(path)) else {
+            continue;
+        };
+
+        let settings = resolver.resolve(&file);
+        let resolved = if file == *path {
+            ResolvedFile::Root(file)
         } else {
-            files.append(&mut self.local_files);
+            let Ok(relative) = file.strip_prefix(base) else {
+                continue;
+            };
+
+            // The walk skips excluded directories, so every directory between
+            // the path and the file must not be excluded either.
+            let depth = file.components().count() - path.components().count();
+            if relative.ancestors().take(depth).any(|entry| is_entry_excluded(entry, settings))
+                || !is_selected(relative, settings)
+            {
+                continue;
+            }
+
+            ResolvedFile::Nested(file)
+        };
+
+        if settings.file_resolver.subset.as_ref().is_some_and(|s| !s.contains(resolved.path())) {
+            continue;
         }
 
-        let local_error = std::mem::replace(&mut self.local_error, Ok(()));
-        if error.is_ok() {
-            *error = local_error;
+        if visit(Ok(resolved)).is_break() {
+            break;
         }
     }
+
+    Ok(())
+}
+
+/// Check whether an entry of the walk, relative to the root, is excluded by
+/// the built-in or the user exclusions.
+fn is_entry_excluded(path: &Path, settings: &Settings) -> bool {
+    let file_path = Candidate::new(path);
+    let file_basename = Candidate::new(path.file_name().unwrap_or_default());
+
+    // @@Todo: we should combine `exclude` and `user_exclude` into a single
+    // exclusion set.
+    match_candidate_exclusion(&file_path, &file_basename, &settings.file_resolver.exclude)
+        || match_candidate_exclusion(
+            &file_path,
+            &file_basename,
+            &settings.file_resolver.user_exclude,
+        )
+}
+
+/// Check whether a file, relative to the root, is included and matches the
+/// selected file types and globs.
+fn is_selected(path: &Path, settings: &Settings) -> bool {
+    let file_path = Candidate::new(path);
+    let file_basename = Candidate::new(path.file_name().unwrap_or_default());
+    let is_selected = |set: &GlobSet| {
+        set.is_empty() || match_candidate_exclusion(&file_path, &file_basename, set)
+    };
+
+    settings.file_resolver.include.is_match(path)
+        && is_selected(&settings.file_resolver.types)
+        && is_selected(&settings.file_resolver.globs)
+}
+
+/// Convert the result of visiting a file into the state of the walk.
+fn visit_state(flow: ControlFlow<()>) -> WalkState {
+    match flow {
+        ControlFlow::Continue(()) => WalkState::Continue,
+        ControlFlow::Break(()) => WalkState::Quit,
+    }
 }
 
 pub fn match_candidate_exclusion(
@@ -321,3 +424,44 @@ impl serde::Serialize for ResolvedFile {
         self.path().serialize(serializer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{settings::FilePattern, testing::repository};
+
+    #[test]
+    fn test_visit_revision_files() {
+        let repo = repository(&[
+            ("src/main.rs", ""),
+            ("src/vendor/lib.rs", ""),
+            ("docs/README.md", ""),
+            ("web/app.ts", ""),
+        ]);
+        let root = repo.path().to_path_buf();
+
+        // The files are taken from the revision, rather than the working tree.
+        std::fs::remove_file(root.join("docs/README.md")).unwrap();
+        std::fs::write(root.join("src/untracked.rs"), "").unwrap();
+
+        let mut settings = Settings::new(true, root.join("CODEOWNERS"));
+        settings.file_resolver.include =
+            settings.file_resolver.include.extend([FilePattern::all()]).unwrap();
+        settings.file_resolver.select_files(&[], &[], &["!src/vendor/**".into()]).unwrap();
+        settings.revision = Some(Revision::new(&root, "HEAD").unwrap());
+
+        let paths = [root.join("src"), root.join("docs"), root.join("web/app.ts")];
+        let files = find_files_in_paths(&root, &paths, &settings, None).unwrap();
+        let mut files = files.into_iter().map(|file| file.unwrap()).collect_vec();
+        files.sort_by(|a, b| a.path().cmp(b.path()));
+
+        assert_eq!(
+            files.iter().map(|file| (file.path().clone(), file.is_root())).collect_vec(),
+            [
+                (root.join("docs/README.md"), false),
+                (root.join("src/main.rs"), false),
+                (root.join("web/app.ts"), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Implementation of the roster, which maps the people that contribute to a
+//! repository to the teams that they belong to. The roster is a JSON object
+//! with the members of each team, where each member is either their email
+//! address or their name as it appears in the git history:
+//!
+//! ```json
+//! {
+//!     "@org/core": ["alice@example.com", "Bob Smith"],
+//!     "@org/web": ["carol@example.com"]
+//! }
+//! ```
+
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Result, anyhow};
+
+#[derive(Debug, Default)]
+pub struct Roster {
+    /// The teams of each member, keyed by their lowercased email address or
+    /// name.
+    teams: HashMap<String, Vec<String>>,
+}
+
+impl Roster {
+    /// Read and parse the roster at the given path.
+    pub fn parse_from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("Failed to read the roster at {path:?}: {err}."))?;
+        Self::parse(&contents).map_err(|err| anyhow!("Invalid roster at {path:?}: {err}."))
+    }
+
+    /// Parse the `contents` of a roster.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let members: HashMap<String, Vec<String>> = serde_json::from_str(contents)?;
+
+        let mut roster = Roster::default();
+        for (team, members) in members {
+            for member in members {
+                let teams = roster.teams.entry(member.to_lowercase()).or_default();
+                if !teams.contains(&team) {
+                    teams.push(team.clone());
+                }
+            }
+        }
+
+        // The teams are sorted so that the output doesn't depend on the order
+        // of the object.
+        roster.teams.values_mut().for_each(|teams| teams.sort());
+        Ok(roster)
+    }
+
+    /// Get the teams of the author with the given email address and name. The
+    /// email address takes precedence over the name.
+    pub fn teams_of(&self, email: &str, name: &str) -> &[String] {
+        self.teams
+            .get(&email.to_lowercase())
+            .or_else(|| self.teams.get(&name.to_lowercase()))
+            .map_or(&[], |teams| teams)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roster_teams_of() {
+        let roster = Roster::parse(
+            r#"{
+                "@org/core": ["alice@example.com", "Bob Smith"],
+                "@org/web": ["Alice@Example.com"]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(roster.teams_of("alice@example.com", "Alice"), ["@org/core", "@org/web"]);
+        assert_eq!(roster.teams_of("bob@example.com", "bob smith"), ["@org/core"]);
+        assert!(roster.teams_of("carol@example.com", "Carol").is_empty());
+        assert!(Roster::parse("[]").is_err());
+    }
+}
@@ -1,10 +1,18 @@
 //! Defines all of the settings that a [super::Workspace] can hold.
 
-use std::{fmt, ops::Deref, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashSet,
+    fmt,
+    ops::Deref,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::Result;
 use globset::{Glob, GlobSet, GlobSetBuilder};
 
+use crate::{git::Revision, types::patterns_for_types};
+
 #[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
 pub enum FilePattern {
     Builtin(&'static str),
@@ -142,8 +150,20 @@ pub struct FileResolverSettings {
     /// Any user extensions to the exclusion patterns.
     pub user_exclude: FilePatternSet,
 
+    /// The globs of the file types that files must belong to. If this is
+    /// empty, files of any type are accepted.
+    pub types: FilePatternSet,
+
+    /// Any user globs that files must match. If this is empty, all files
+    /// are accepted.
+    pub globs: FilePatternSet,
+
     /// Whether to enforce file exclusions.
     pub force_exclude: bool,
+
+    /// The only files that may be selected, e.g. the files that have changed
+    /// since a git revision. If this is `None`, any file may be selected.
+    pub subset: Option<FileSubset>,
 }
 
 impl FileResolverSettings {
@@ -152,11 +172,80 @@ impl FileResolverSettings {
             include: FilePatternSet::try_from_iter(INCLUDE.iter().cloned()).unwrap(),
             exclude: FilePatternSet::try_from_iter(EXCLUDE.iter().cloned()).unwrap(),
             user_exclude: FilePatternSet::default(),
+            types: FilePatternSet::default(),
+            globs: FilePatternSet::default(),
             force_exclude: false,
+            subset: None,
         }
     }
 }
 
+impl FileResolverSettings {
+    /// Restrict the files to the given file `types` and user `globs`, and
+    /// exclude the files of the `types_not`. Globs that are prefixed with `!`
+    /// exclude the matching files instead.
+    pub fn select_files(
+        &mut self,
+        types: &[String],
+        types_not: &[String],
+        globs: &[String],
+    ) -> Result<()> {
+        let (excluded, included): (Vec<_>, Vec<_>) =
+            globs.iter().partition(|glob| glob.starts_with('!'));
+
+        self.types = FilePatternSet::try_from_iter(patterns_for_types(types)?)?;
+        self.globs =
+            FilePatternSet::try_from_iter(included.into_iter().map(FilePattern::new_user))?;
+
+        let mut exclusions = patterns_for_types(types_not)?;
+        exclusions.extend(excluded.into_iter().map(|glob| FilePattern::new_user(&glob[1..])));
+        self.user_exclude = std::mem::take(&mut self.user_exclude).extend(exclusions)?;
+
+        Ok(())
+    }
+}
+
+/// A known set of files that the selected files are restricted to.
+#[derive(Debug, Clone, Default)]
+pub struct FileSubset {
+    /// The absolute paths of the files.
+    files: HashSet<PathBuf>,
+
+    /// All of the directories that contain any of the files, which allows
+    /// skipping the directories that don't.
+    dirs: HashSet<PathBuf>,
+}
+
+impl FileSubset {
+    /// Create a subset from the absolute paths of the files.
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        let files = files.into_iter().collect::<HashSet<_>>();
+        let dirs = files.iter().flat_map(|file| file.ancestors().skip(1)).map(Path::to_path_buf);
+
+        FileSubset { dirs: dirs.collect(), files }
+    }
+
+    /// Restrict the subset to the files that are also within `other`.
+    pub fn intersect(self, other: &FileSubset) -> Self {
+        FileSubset::new(self.files.into_iter().filter(|file| other.contains(file)))
+    }
+
+    /// Check whether the given file is within the subset.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains(path)
+    }
+
+    /// Check whether the given directory contains any of the files.
+    pub fn contains_dir(&self, path: &Path) -> bool {
+        self.dirs.contains(path)
+    }
+
+    /// Iterate over all of the files of the subset, in no particular order.
+    pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+        self.files.iter()
+    }
+}
+
 pub struct Settings {
     /// Whether we should or shouldn't look at files that are within the
     /// CODEOWNERS.
@@ -167,11 +256,25 @@ pub struct Settings {
 
     /// Settings to do with file exclusions/inclusions.
     pub file_resolver: FileResolverSettings,
+
+    /// The git revision that the files and the CODEOWNERS file are read
+    /// from. If this is `None`, they're read from the file system.
+    pub revision: Option<Revision>,
+
+    /// The number of threads that are used to walk and search the files. If
+    /// this is `None`, the number is based on the available parallelism.
+    pub threads: Option<usize>,
 }
 
 impl Settings {
     pub fn new(respect_gitignore: bool, codeowners: PathBuf) -> Self {
-        Settings { respect_gitignore, codeowners, file_resolver: FileResolverSettings::new() }
+        Settings {
+            respect_gitignore,
+            codeowners,
+            file_resolver: FileResolverSettings::new(),
+            revision: None,
+            threads: None,
+        }
     }
 }
 
@@ -211,4 +314,47 @@ mod tests {
         assert!(set.is_match("app/notes/domain/team-code/"));
         assert!(!set.is_match("app/notes/sub/index.js"));
     }
+
+    #[test]
+    fn test_file_subset() {
+        let subset = FileSubset::new(
+            ["/repo/src/main.rs", "/repo/src/api/a.rs", "/repo/README.md"].map(PathBuf::from),
+        );
+
+        assert!(subset.contains(Path::new("/repo/src/api/a.rs")));
+        assert!(!subset.contains(Path::new("/repo/src/lib.rs")));
+        assert!(subset.contains_dir(Path::new("/repo/src/api")));
+        assert!(subset.contains_dir(Path::new("/repo")));
+        assert!(!subset.contains_dir(Path::new("/repo/docs")));
+
+        let other = FileSubset::new(["/repo/src/api/a.rs", "/repo/web/app.ts"].map(PathBuf::from));
+        let intersection = subset.intersect(&other);
+        assert_eq!(intersection.files().collect::<Vec<_>>(), [Path::new("/repo/src/api/a.rs")]);
+        assert!(!intersection.contains_dir(Path::new("/repo/src/main.rs")));
+        assert!(!intersection.contains_dir(Path::new("/repo/web")));
+    }
+
+    #[test]
+    fn test_select_files() {
+        let mut settings = FileResolverSettings::new();
+        settings
+            .select_files(
+                &["rust".into(), "ts".into()],
+                &["json".into()],
+                &["src/**".into(), "!src/vendor/**".into()],
+            )
+            .unwrap();
+
+        assert!(settings.types.is_match("main.rs"));
+        assert!(settings.types.is_match("app.tsx"));
+        assert!(!settings.types.is_match("README.md"));
+
+        assert!(settings.globs.is_match("src/main.rs"));
+        assert!(!settings.globs.is_match("lib/main.rs"));
+
+        assert!(settings.user_exclude.is_match("package.json"));
+        assert!(settings.user_exclude.is_match("src/vendor/lib.rs"));
+
+        assert!(settings.select_files(&["klingon".into()], &[], &[]).is_err());
+    }
 }
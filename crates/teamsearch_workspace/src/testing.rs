@@ -0,0 +1,60 @@
+//! Utilities for testing against git repositories, which are shared with the
+//! tests of the other crates through the `testing` feature.
+
+use std::{fs, path::Path, process::Command};
+
+use tempfile::TempDir;
+
+/// The author of the commits that don't specify one.
+pub const AUTHOR: (&str, &str) = ("Carol", "carol@example.com");
+
+/// Run `git` within `dir` as the given `(name, email)` author, returning its
+/// output.
+pub fn git_as(dir: &Path, (name, email): (&str, &str), args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", &format!("user.name={name}"), "-c", &format!("user.email={email}")])
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run `git` within `dir` as the default [AUTHOR], returning its output.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    git_as(dir, AUTHOR, args)
+}
+
+/// Write the given files within `dir`, without staging them.
+pub fn write(dir: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+/// Write the given files within `dir`, and commit them with `message` as
+/// `author`.
+pub fn commit_as(dir: &Path, author: (&str, &str), files: &[(&str, &str)], message: &str) {
+    write(dir, files);
+    git_as(dir, author, &["add", "-A"]);
+    git_as(dir, author, &["commit", "-q", "-m", message]);
+}
+
+/// Write the given files within `dir`, and commit them with `message` as the
+/// default [AUTHOR].
+pub fn commit(dir: &Path, files: &[(&str, &str)], message: &str) {
+    commit_as(dir, AUTHOR, files, message);
+}
+
+/// Create a repository with a commit of the given files.
+pub fn repository(files: &[(&str, &str)]) -> TempDir {
+    let dir = TempDir::new().unwrap();
+    git(dir.path(), &["init", "-q"]);
+    commit(dir.path(), files, "Initial commit");
+    dir
+}
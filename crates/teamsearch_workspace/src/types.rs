@@ -0,0 +1,75 @@
+//! Built-in definitions of file types, mapping the name of a language (or a
+//! file format) to the globs of the files that belong to it. These can be used
+//! to narrow down the files that are searched, e.g. `--type rust`.
+
+use anyhow::{Result, anyhow};
+
+use crate::settings::FilePattern;
+
+/// The table of all of the known file types, sorted by name.
+pub static FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cmake", &["*.cmake", "CMakeLists.txt"]),
+    ("cpp", &["*.cc", "*.cpp", "*.cxx", "*.c++", "*.h", "*.hh", "*.hpp", "*.hxx", "*.inl"]),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("dart", &["*.dart"]),
+    ("docker", &["Dockerfile", "*.Dockerfile", "*.dockerfile", "Dockerfile.*"]),
+    ("elixir", &["*.ex", "*.exs", "*.eex", "*.heex", "*.leex"]),
+    ("erlang", &["*.erl", "*.hrl"]),
+    ("go", &["*.go"]),
+    ("graphql", &["*.graphql", "*.graphqls", "*.gql"]),
+    ("haskell", &["*.hs", "*.lhs"]),
+    ("html", &["*.htm", "*.html", "*.xhtml"]),
+    ("java", &["*.java", "*.jsp", "*.jspx", "*.properties"]),
+    ("js", &["*.js", "*.jsx", "*.cjs", "*.mjs", "*.vue"]),
+    ("json", &["*.json", "*.jsonc", "*.json5"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("lua", &["*.lua"]),
+    ("make", &["Makefile", "GNUmakefile", "makefile", "*.mk", "*.mak"]),
+    ("markdown", &["*.markdown", "*.md", "*.mdx", "*.mkd", "*.mkdn"]),
+    ("nix", &["*.nix"]),
+    ("objc", &["*.h", "*.m"]),
+    ("ocaml", &["*.ml", "*.mli", "*.mll", "*.mly"]),
+    ("perl", &["*.perl", "*.pl", "*.pm", "*.pod", "*.t"]),
+    ("php", &["*.php", "*.php3", "*.php4", "*.php5", "*.phtml"]),
+    ("proto", &["*.proto"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("r", &["*.R", "*.r", "*.Rmd", "*.Rrst"]),
+    ("ruby", &["*.rb", "*.rbw", "*.gemspec", "Gemfile", "Rakefile", ".irbrc"]),
+    ("rust", &["*.rs"]),
+    ("scala", &["*.scala", "*.sbt"]),
+    ("sh", &["*.bash", "*.sh", "*.zsh", ".bashrc", ".bash_profile", ".zshrc", ".profile"]),
+    ("sql", &["*.sql", "*.psql"]),
+    ("svelte", &["*.svelte"]),
+    ("swift", &["*.swift"]),
+    ("terraform", &["*.tf", "*.tfvars", "*.tf.json"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx", "*.cts", "*.mts"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml", "*.xml.dist", "*.xsd", "*.xsl", "*.xslt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+    ("zig", &["*.zig"]),
+];
+
+/// Get the globs for a given file type, if it is known.
+pub fn globs_for_type(name: &str) -> Option<&'static [&'static str]> {
+    FILE_TYPES.iter().find(|(ty, _)| *ty == name).map(|(_, globs)| *globs)
+}
+
+/// Convert the given file type names into the [FilePattern]s that select
+/// the files of those types.
+pub fn patterns_for_types<S: AsRef<str>>(names: &[S]) -> Result<Vec<FilePattern>> {
+    let mut patterns = vec![];
+
+    for name in names {
+        let name = name.as_ref();
+        let globs = globs_for_type(name).ok_or_else(|| {
+            anyhow!("Unrecognized file type `{name}`, see `--type-list` for all known types.")
+        })?;
+
+        patterns.extend(globs.iter().copied().map(FilePattern::Builtin));
+    }
+
+    Ok(patterns)
+}